@@ -1,17 +1,415 @@
 #![deny(clippy::all, clippy::pedantic, clippy::unwrap_used)]
 
-use std::path::PathBuf;
+mod meta;
+mod progress;
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
-static DEFAULT_FILENAME: &str = "out.cbz";
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand, ValueEnum};
+use reco::{
+    ErrorPolicy, Naming, PageSelection, Report,
+    backend::NameEncoding,
+    encoding::{
+        ChromaSubsampling as EncodingChromaSubsampling, EncodingOptions,
+        ImageFormat as EncodingImageFormat,
+    },
+    reader::{Options as ReaderOptions, SpineOrder},
+    writer::{Compression as WriterCompression, Format as WriterFormat, Options as WriterOptions},
+};
+use reco_edit::{Flip as EditFlip, Operation as EditOperation, Rotation as EditRotation};
+use reco_meta::{Change as MetaChange, Field as MetaField};
+use reco_split::Strategy as SplitStrategy;
+
+static DEFAULT_FILE_STEM: &str = "out";
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum OutputFormat {
+    #[default]
+    Cbz,
+    Epub,
+}
+
+impl From<OutputFormat> for WriterFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Cbz => Self::Cbz,
+            OutputFormat::Epub => Self::Epub,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum PageOrder {
+    /// By file name, numbers being compared by value
+    #[default]
+    Natural,
+    /// By file name, character by character
+    Lexical,
+    /// As stored in the archive
+    Archive,
+    /// As listed by the `ComicInfo.xml` pages
+    ComicInfo,
+}
+
+impl From<PageOrder> for SpineOrder {
+    fn from(order: PageOrder) -> Self {
+        match order {
+            PageOrder::Natural => Self::Natural,
+            PageOrder::Lexical => Self::Lexical,
+            PageOrder::Archive => Self::Archive,
+            PageOrder::ComicInfo => Self::ComicInfo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum OnError {
+    /// Stop at the first page which can't be read or written
+    Fail,
+    /// Skip the page
+    #[default]
+    Skip,
+    /// Replace the page by a blank one
+    Placeholder,
+}
+
+impl From<OnError> for ErrorPolicy {
+    fn from(on_error: OnError) -> Self {
+        match on_error {
+            OnError::Fail => Self::FailFast,
+            OnError::Skip => Self::Skip,
+            OnError::Placeholder => Self::Placeholder,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum Compression {
+    #[default]
+    Auto,
+    Stored,
+    Deflate,
+    Zstd,
+}
+
+impl From<Compression> for WriterCompression {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::Auto => Self::Auto,
+            Compression::Stored => Self::Stored,
+            Compression::Deflate => Self::Deflate,
+            Compression::Zstd => Self::Zstd,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+    Avif,
+}
+
+impl From<ImageFormat> for EncodingImageFormat {
+    fn from(format: ImageFormat) -> Self {
+        match format {
+            ImageFormat::Jpeg => Self::Jpeg,
+            ImageFormat::Png => Self::Png,
+            ImageFormat::Webp => Self::WebP,
+            ImageFormat::Avif => Self::Avif,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ChromaSubsampling {
+    #[value(name = "420")]
+    Yuv420,
+    #[value(name = "422")]
+    Yuv422,
+    #[value(name = "444")]
+    Yuv444,
+}
+
+impl From<ChromaSubsampling> for EncodingChromaSubsampling {
+    fn from(chroma_subsampling: ChromaSubsampling) -> Self {
+        match chroma_subsampling {
+            ChromaSubsampling::Yuv420 => Self::Yuv420,
+            ChromaSubsampling::Yuv422 => Self::Yuv422,
+            ChromaSubsampling::Yuv444 => Self::Yuv444,
+        }
+    }
+}
+
+/// Images are inserted unchanged (when possible) unless one of these options is set.
+#[derive(Debug, clap::Args)]
+struct EncodingArgs {
+    /// The format the images are encoded in (defaults to jpeg, or png when lossless)
+    #[clap(long, value_enum)]
+    image_format: Option<ImageFormat>,
+
+    /// The encoding quality, from 1 to 100 (jpeg and avif only, defaults to 75)
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+
+    /// Encode the images without any loss (png and webp only)
+    #[clap(long, action)]
+    lossless: bool,
+
+    /// The chroma subsampling of jpeg images (defaults to 420)
+    #[clap(long, value_enum)]
+    chroma_subsampling: Option<ChromaSubsampling>,
+}
+
+impl EncodingArgs {
+    fn options(&self) -> Result<Option<EncodingOptions>> {
+        if self.image_format.is_none()
+            && self.quality.is_none()
+            && !self.lossless
+            && self.chroma_subsampling.is_none()
+        {
+            return Ok(None);
+        }
+
+        let format = match self.image_format {
+            Some(format) => format.into(),
+            None if self.lossless => EncodingImageFormat::Png,
+            None => EncodingImageFormat::default(),
+        };
+        if self.lossless && !format.supports_lossless() {
+            bail!("{} images can't be encoded losslessly", format.extension());
+        }
+        if self.quality.is_some() && !format.supports_lossy() {
+            bail!(
+                "{} images are always encoded losslessly, --quality doesn't apply",
+                format.extension()
+            );
+        }
+
+        let mut opts = EncodingOptions::new(format).with_lossless(self.lossless);
+        if let Some(quality) = self.quality {
+            opts = opts.with_quality(quality);
+        }
+        if let Some(chroma_subsampling) = self.chroma_subsampling {
+            opts = opts.with_chroma_subsampling(chroma_subsampling.into());
+        }
+
+        Ok(Some(opts))
+    }
+}
+
+/// Where an archive is split, exactly one of these options is required.
+#[derive(Debug, clap::Args)]
+#[group(required = true, multiple = false)]
+struct SplitArgs {
+    /// Parts of (at most) this many pages
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pages: Option<u64>,
+
+    /// Parts of (at most) this size of images, e.g. 50M or 1.5G
+    #[clap(long, value_parser = parse_size)]
+    size: Option<u64>,
+
+    /// A part per top-level chapter (folder or `ComicInfo.xml` bookmark)
+    #[clap(long, action)]
+    chapters: bool,
+
+    /// Parts starting at these page numbers (starting at 1), e.g. 20,45
+    #[clap(long, value_delimiter = ',')]
+    at: Vec<usize>,
+}
+
+impl SplitArgs {
+    fn strategy(self) -> Result<SplitStrategy> {
+        Ok(if let Some(pages) = self.pages {
+            SplitStrategy::PageCount(usize::try_from(pages)?)
+        } else if let Some(size) = self.size {
+            SplitStrategy::Size(size)
+        } else if self.chapters {
+            SplitStrategy::Chapters
+        } else {
+            SplitStrategy::At(self.at)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Rotation {
+    #[value(name = "90")]
+    Rotate90,
+    #[value(name = "180")]
+    Rotate180,
+    #[value(name = "270")]
+    Rotate270,
+}
+
+impl From<Rotation> for EditRotation {
+    fn from(rotation: Rotation) -> Self {
+        match rotation {
+            Rotation::Rotate90 => Self::Rotate90,
+            Rotation::Rotate180 => Self::Rotate180,
+            Rotation::Rotate270 => Self::Rotate270,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Flip {
+    Horizontal,
+    Vertical,
+}
+
+impl From<Flip> for EditFlip {
+    fn from(flip: Flip) -> Self {
+        match flip {
+            Flip::Horizontal => Self::Horizontal,
+            Flip::Vertical => Self::Vertical,
+        }
+    }
+}
+
+/// The edited archive, pages are numbered from 1.
+#[derive(Debug, clap::Args)]
+struct EditArgs {
+    /// Path to the archive
+    path: PathBuf,
+
+    /// Where the edited archive is written as a cbz (defaults to the archive itself)
+    #[clap(long, short)]
+    output: Option<PathBuf>,
+
+    /// How the pages are ordered, and so numbered
+    #[clap(long, value_enum, default_value_t)]
+    order: PageOrder,
+}
+
+#[derive(Debug, Subcommand)]
+enum EditCommand {
+    /// Delete pages
+    Delete {
+        #[clap(flatten)]
+        archive: EditArgs,
+
+        /// The pages to delete, e.g. 1-3,25
+        pages: PageSelection,
+    },
+
+    /// Move pages before another page
+    Move {
+        #[clap(flatten)]
+        archive: EditArgs,
+
+        /// The pages to move, e.g. 1-3,25
+        pages: PageSelection,
+
+        /// The page the moved pages are placed before (the page count + 1 for the end)
+        #[clap(long)]
+        before: usize,
+    },
+
+    /// Insert images before a page
+    Insert {
+        #[clap(flatten)]
+        archive: EditArgs,
+
+        /// The images to insert, in order
+        #[clap(required = true)]
+        images: Vec<PathBuf>,
+
+        /// The page the images are inserted before (defaults to the end)
+        #[clap(long)]
+        before: Option<usize>,
+    },
+
+    /// Rotate pages clockwise
+    Rotate {
+        #[clap(flatten)]
+        archive: EditArgs,
+
+        /// The pages to rotate, e.g. 1-3,25
+        pages: PageSelection,
+
+        /// The clockwise rotation, in degrees
+        #[clap(long, value_enum)]
+        angle: Rotation,
+
+        #[clap(flatten)]
+        encoding: EncodingArgs,
+    },
+
+    /// Flip pages
+    Flip {
+        #[clap(flatten)]
+        archive: EditArgs,
+
+        /// The pages to flip, e.g. 1-3,25
+        pages: PageSelection,
+
+        #[clap(long, value_enum)]
+        direction: Flip,
+
+        #[clap(flatten)]
+        encoding: EncodingArgs,
+    },
+
+    /// Swap two pages
+    Swap {
+        #[clap(flatten)]
+        archive: EditArgs,
+
+        a: usize,
+
+        b: usize,
+    },
+}
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 struct Args {
     #[clap(subcommand)]
     command: Command,
+
+    /// The encoding of the zip entry names not flagged as UTF-8: auto (UTF-8, a detected
+    /// Japanese, Chinese or Korean encoding, or CP437), cp437 or e.g. `shift_jis` or `gbk`
+    #[clap(long, global = true, default_value = "auto")]
+    name_encoding: NameEncoding,
+}
+
+/// How the archives written by pack, merge and convert are produced.
+#[derive(Debug, Clone, clap::Args)]
+struct JobArgs {
+    /// What to do with the pages which can't be read or written, the exit code is non-zero when
+    /// pages are skipped or replaced
+    #[clap(long, value_enum, default_value_t)]
+    on_error: OnError,
+
+    /// Fail instead of replacing an existing archive
+    #[clap(long, action)]
+    no_clobber: bool,
+
+    /// How the pages are named in cbz archives: index (the default), index:<width>, original (the
+    /// source file names) or a template with {page}, {chapter} and {name} placeholders, e.g.
+    /// "Chapter {chapter}/{page:3}"
+    #[clap(long)]
+    naming: Option<Naming>,
+
+    /// How the pages of cbz archives are compressed (auto, the default, stores jpeg and webp
+    /// images, and deflates the others)
+    #[clap(long, value_enum)]
+    compression: Option<Compression>,
+
+    /// The compression level, 0 to 9 for deflate and -7 to 22 for zstd
+    #[clap(long, allow_negative_numbers = true)]
+    compression_level: Option<i64>,
+
+    /// A file added as is to the cbz archives (e.g. a metadata file), can be repeated
+    #[clap(long = "sidecar", value_name = "PATH")]
+    sidecars: Vec<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -20,12 +418,26 @@ enum Command {
         /// Path to the source file
         path: PathBuf,
 
-        /// Path to the cbz file
+        /// Path to the output directory
         #[clap(default_value = ".")]
         output: PathBuf,
 
-        /// The file name of the archive file (defaults to the file stem + cbz or out.cbz if none is found)
+        /// The file name of the archive file (defaults to the file stem + extension or out + extension if none is found)
         filename: Option<String>,
+
+        /// The format of the produced archive
+        #[clap(long, value_enum, default_value_t)]
+        to: OutputFormat,
+
+        #[clap(flatten)]
+        encoding: EncodingArgs,
+
+        /// The number of threads images are processed on (defaults to one per core)
+        #[clap(long, short, default_value_t = 0, hide_default_value = true)]
+        jobs: usize,
+
+        #[clap(flatten)]
+        job: JobArgs,
     },
 
     Pack {
@@ -36,13 +448,26 @@ enum Command {
         #[clap(default_value = ".")]
         output: PathBuf,
 
-        /// The file name of the archive file
-        #[clap(default_value = DEFAULT_FILENAME)]
-        filename: String,
+        /// The file name of the archive file (defaults to out + extension)
+        filename: Option<String>,
 
         /// Automatically split landscape images into 2 pages
         #[clap(long, action)]
         autosplit: bool,
+
+        /// The format of the produced archive
+        #[clap(long, value_enum, default_value_t)]
+        to: OutputFormat,
+
+        #[clap(flatten)]
+        encoding: EncodingArgs,
+
+        /// The number of threads images are processed on (defaults to one per core)
+        #[clap(long, short, default_value_t = 0, hide_default_value = true)]
+        jobs: usize,
+
+        #[clap(flatten)]
+        job: JobArgs,
     },
 
     Merge {
@@ -53,9 +478,104 @@ enum Command {
         #[clap(default_value = ".")]
         output: PathBuf,
 
-        /// The file name of the archive file
-        #[clap(default_value = DEFAULT_FILENAME)]
-        filename: String,
+        /// The file name of the archive file (defaults to out + extension)
+        filename: Option<String>,
+
+        /// The format of the produced archive
+        #[clap(long, value_enum, default_value_t)]
+        to: OutputFormat,
+
+        /// How the pages of each merged archive are ordered
+        #[clap(long, value_enum, default_value_t)]
+        order: PageOrder,
+
+        /// Append the pages to the output archive if it exists (cbz only)
+        #[clap(long, action)]
+        append: bool,
+
+        #[clap(flatten)]
+        encoding: EncodingArgs,
+
+        #[clap(flatten)]
+        job: JobArgs,
+    },
+
+    /// Extract the pages of an archive into a directory, named after their page number
+    Extract {
+        /// Path to the archive
+        path: PathBuf,
+
+        /// The output directory (defaults to the archive file stem)
+        output: Option<PathBuf>,
+
+        /// The pages to extract, starting at 1, e.g. 1-10,25 or 30- (defaults to all)
+        #[clap(long)]
+        pages: Option<PageSelection>,
+
+        /// How the pages are ordered, and so numbered
+        #[clap(long, value_enum, default_value_t)]
+        order: PageOrder,
+
+        #[clap(flatten)]
+        encoding: EncodingArgs,
+    },
+
+    /// Split an archive into numbered parts, named after the archive file stem
+    Split {
+        /// Path to the archive
+        path: PathBuf,
+
+        /// The output directory for the parts
+        #[clap(default_value = ".")]
+        output: PathBuf,
+
+        #[clap(flatten)]
+        strategy: SplitArgs,
+
+        /// The format of the produced archives
+        #[clap(long, value_enum, default_value_t)]
+        to: OutputFormat,
+
+        /// How the pages are ordered before being split
+        #[clap(long, value_enum, default_value_t)]
+        order: PageOrder,
+
+        /// Fail instead of replacing existing parts
+        #[clap(long, action)]
+        no_clobber: bool,
+
+        #[clap(flatten)]
+        encoding: EncodingArgs,
+    },
+
+    /// Edit the pages of an archive, rewritten in place unless an output is given
+    Edit {
+        #[clap(subcommand)]
+        command: EditCommand,
+    },
+
+    /// Print or edit the metadata (`ComicInfo.xml` and zip comment) of archives
+    Meta {
+        /// A glob pattern that matches the archive files to inspect or edit
+        pattern: String,
+
+        /// Set a field, edits the archives in place (title, series, number, volume, writer,
+        /// publisher, language, reading-direction, comment, or any other `ComicInfo` element)
+        #[clap(long = "set", value_name = "FIELD=VALUE", value_parser = parse_field_value)]
+        set: Vec<(MetaField, String)>,
+
+        /// Unset a field, edits the archives in place
+        #[clap(long = "unset", value_name = "FIELD")]
+        unset: Vec<MetaField>,
+
+        /// Print the metadata as JSON
+        #[clap(long, action)]
+        json: bool,
+
+        /// Rewrite the archives whose entry names aren't stored in UTF-8 (e.g. Shift-JIS) with
+        /// UTF-8 names, decoded with the --name-encoding
+        #[clap(long, action, conflicts_with_all = ["set", "unset", "json"])]
+        repair_names: bool,
     },
 
     View {
@@ -73,37 +593,449 @@ fn main() -> Result<()> {
             path,
             output,
             filename,
-        } => {
-            let filename = filename
-                .or_else(|| {
-                    path.file_stem()
-                        .and_then(|stem| stem.to_str())
-                        .map(|stem| format!("{stem}.cbz"))
-                })
-                .unwrap_or_else(|| DEFAULT_FILENAME.to_string());
-            let output_path = output.join(filename);
-            reco_convert::convert(&path, &output_path)?;
-        }
+            to,
+            encoding,
+            jobs,
+            job,
+        } => convert(
+            &path,
+            &output,
+            filename,
+            to,
+            &encoding,
+            jobs,
+            &job,
+            args.name_encoding,
+        )?,
         Command::Pack {
             pattern,
             output,
             filename,
             autosplit,
-        } => {
-            let path = output.join(filename);
-            let opts = reco_pack::Options::new(autosplit);
-            reco_pack::pack(&pattern, &path, opts)?;
-        }
+            to,
+            encoding,
+            jobs,
+            job,
+        } => pack(
+            &pattern, &output, filename, autosplit, to, &encoding, jobs, &job,
+        )?,
         Command::Merge {
             pattern,
             output,
             filename,
+            to,
+            order,
+            append,
+            encoding,
+            job,
+        } => merge(
+            &pattern,
+            &output,
+            filename,
+            to,
+            order,
+            append,
+            &encoding,
+            &job,
+            args.name_encoding,
+        )?,
+        Command::Extract {
+            path,
+            output,
+            pages,
+            order,
+            encoding,
+        } => extract(&path, output, pages, order, &encoding, args.name_encoding)?,
+        Command::Split {
+            path,
+            output,
+            strategy,
+            to,
+            order,
+            no_clobber,
+            encoding,
+        } => split(
+            &path,
+            &output,
+            strategy,
+            to,
+            order,
+            no_clobber,
+            &encoding,
+            args.name_encoding,
+        )?,
+        Command::Edit { command } => edit(command, args.name_encoding)?,
+        Command::Meta {
+            pattern,
+            set,
+            unset,
+            json,
+            repair_names,
+        } => edit_meta(&pattern, set, unset, json, repair_names, args.name_encoding)?,
+        Command::View { path } => reco_view::view_with_options(
+            &path,
+            ReaderOptions::default().with_name_encoding(args.name_encoding),
+        )?,
+    }
+
+    Ok(())
+}
+
+/// Converts the file to an archive in `output`, named after the file stem unless `filename` is set.
+#[expect(clippy::too_many_arguments)]
+fn convert(
+    path: &Path,
+    output: &Path,
+    filename: Option<String>,
+    to: OutputFormat,
+    encoding: &EncodingArgs,
+    jobs: usize,
+    job: &JobArgs,
+    name_encoding: NameEncoding,
+) -> Result<()> {
+    let format = WriterFormat::from(to);
+    let filename = filename
+        .or_else(|| {
+            path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(|stem| format!("{stem}.{}", format.extension()))
+        })
+        .unwrap_or_else(|| default_filename(format));
+    let output_path = output.join(filename);
+    let opts = reco_convert::Options::default()
+        .with_reader_options(ReaderOptions::default().with_name_encoding(name_encoding))
+        .with_writer_options(writer_options(
+            format,
+            &output_path,
+            encoding.options()?,
+            job,
+        )?)
+        .with_jobs(jobs)
+        .with_error_policy(job.on_error.into());
+    let bar = progress::PagesBar::new();
+    let report = reco_convert::convert_with_progress(path, &output_path, opts, &bar)?;
+    bar.finish();
+
+    check_report(&report)
+}
+
+/// Packs the files matching the pattern into an archive in `output`.
+#[expect(clippy::too_many_arguments)]
+fn pack(
+    pattern: &str,
+    output: &Path,
+    filename: Option<String>,
+    autosplit: bool,
+    to: OutputFormat,
+    encoding: &EncodingArgs,
+    jobs: usize,
+    job: &JobArgs,
+) -> Result<()> {
+    let format = WriterFormat::from(to);
+    let path = output.join(filename.unwrap_or_else(|| default_filename(format)));
+    let opts = reco_pack::Options::new(autosplit)
+        .with_writer_options(writer_options(format, &path, encoding.options()?, job)?)
+        .with_jobs(jobs)
+        .with_error_policy(job.on_error.into());
+    let bar = progress::PagesBar::new();
+    let report = reco_pack::pack_with_progress(pattern, &path, opts, &bar)?;
+    bar.finish();
+
+    check_report(&report)
+}
+
+/// Merges the archives matching the pattern into an archive in `output`.
+#[expect(clippy::too_many_arguments)]
+fn merge(
+    pattern: &str,
+    output: &Path,
+    filename: Option<String>,
+    to: OutputFormat,
+    order: PageOrder,
+    append: bool,
+    encoding: &EncodingArgs,
+    job: &JobArgs,
+    name_encoding: NameEncoding,
+) -> Result<()> {
+    let format = WriterFormat::from(to);
+    let path = output.join(filename.unwrap_or_else(|| default_filename(format)));
+    let opts = reco_merge::Options::default()
+        .with_reader_options(reader_options(order, name_encoding))
+        .with_writer_options(writer_options(format, &path, encoding.options()?, job)?)
+        .with_append(append)
+        .with_error_policy(job.on_error.into());
+    let bar = progress::PagesBar::new();
+    let report = reco_merge::merge_with_progress(pattern, &path, opts, &bar)?;
+    bar.finish();
+
+    check_report(&report)
+}
+
+/// Extracts the pages into `output`, or a directory named after the archive file stem.
+fn extract(
+    path: &Path,
+    output: Option<PathBuf>,
+    pages: Option<PageSelection>,
+    order: PageOrder,
+    encoding: &EncodingArgs,
+    name_encoding: NameEncoding,
+) -> Result<()> {
+    let output = output
+        .unwrap_or_else(|| PathBuf::from(path.file_stem().unwrap_or(DEFAULT_FILE_STEM.as_ref())));
+
+    let mut opts =
+        reco_extract::Options::default().with_reader_options(reader_options(order, name_encoding));
+    if let Some(pages) = pages {
+        opts = opts.with_pages(pages);
+    }
+    if let Some(encoding) = encoding.options()? {
+        opts = opts.with_encoding(encoding);
+    }
+
+    let written = reco_extract::extract(path, &output, &opts)?;
+    println!("{} pages extracted to {}", written.len(), output.display());
+
+    Ok(())
+}
+
+#[expect(clippy::too_many_arguments)]
+fn split(
+    path: &Path,
+    output: &Path,
+    strategy: SplitArgs,
+    to: OutputFormat,
+    order: PageOrder,
+    no_clobber: bool,
+    encoding: &EncodingArgs,
+    name_encoding: NameEncoding,
+) -> Result<()> {
+    let mut writer = WriterOptions::new(to.into()).with_no_clobber(no_clobber);
+    if let Some(encoding) = encoding.options()? {
+        writer = writer.with_encoding(encoding);
+    }
+    let opts = reco_split::Options::new(strategy.strategy()?)
+        .with_reader_options(reader_options(order, name_encoding))
+        .with_writer_options(writer);
+
+    for path in reco_split::split(path, output, &opts)? {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+fn edit(command: EditCommand, name_encoding: NameEncoding) -> Result<()> {
+    let (archive, operation, encoding) = match command {
+        EditCommand::Delete { archive, pages } => (archive, EditOperation::Delete(pages), None),
+        EditCommand::Move {
+            archive,
+            pages,
+            before,
+        } => (archive, EditOperation::Move { pages, before }, None),
+        EditCommand::Insert {
+            archive,
+            images,
+            before,
         } => {
-            let path = output.join(filename);
-            reco_merge::merge(&pattern, &path)?;
+            let operation = EditOperation::Insert {
+                paths: images,
+                before,
+            };
+            (archive, operation, None)
         }
-        Command::View { path } => reco_view::view(&path)?,
+        EditCommand::Rotate {
+            archive,
+            pages,
+            angle,
+            encoding,
+        } => (
+            archive,
+            EditOperation::Rotate(pages, angle.into()),
+            encoding.options()?,
+        ),
+        EditCommand::Flip {
+            archive,
+            pages,
+            direction,
+            encoding,
+        } => (
+            archive,
+            EditOperation::Flip(pages, direction.into()),
+            encoding.options()?,
+        ),
+        EditCommand::Swap { archive, a, b } => (archive, EditOperation::Swap(a, b), None),
+    };
+
+    let mut opts = reco_edit::Options::default()
+        .with_operation(operation)
+        .with_reader_options(reader_options(archive.order, name_encoding));
+    if let Some(encoding) = encoding {
+        opts = opts.with_encoding(encoding);
     }
 
+    let output = archive.output.unwrap_or_else(|| archive.path.clone());
+    reco_edit::edit(&archive.path, &output, &opts)?;
+    println!("{}", output.display());
+
     Ok(())
 }
+
+/// Prints the metadata of the archives, or edits them if any field is set or unset, or repairs
+/// their entry names. Names not flagged as UTF-8 are decoded with the encoding.
+fn edit_meta(
+    pattern: &str,
+    set: Vec<(MetaField, String)>,
+    unset: Vec<MetaField>,
+    json: bool,
+    repair_names: bool,
+    name_encoding: NameEncoding,
+) -> Result<()> {
+    if repair_names {
+        for path in reco_meta::repair(pattern, name_encoding)? {
+            println!("{}", path.display());
+        }
+        return Ok(());
+    }
+
+    let opts = unset
+        .into_iter()
+        .map(MetaChange::Unset)
+        .chain(
+            set.into_iter()
+                .map(|(field, value)| MetaChange::Set(field, value)),
+        )
+        .fold(
+            reco_meta::Options::default().with_name_encoding(name_encoding),
+            reco_meta::Options::with_change,
+        );
+
+    if opts.is_empty() {
+        meta::print(&reco_meta::inspect(pattern, name_encoding)?, json)?;
+    } else {
+        for path in reco_meta::edit(pattern, &opts)? {
+            println!("{}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the inputs which were skipped or replaced, failing if there are any.
+fn check_report(report: &Report) -> Result<()> {
+    if report.is_complete() {
+        return Ok(());
+    }
+
+    for failure in &report.failures {
+        let action = if failure.placeholder {
+            "replaced"
+        } else {
+            "skipped"
+        };
+        eprintln!("{action} {}: {}", failure.input, failure.error);
+    }
+
+    bail!(
+        "{} pages written, failed inputs: {}",
+        report.pages,
+        report.failures.len()
+    )
+}
+
+/// A size in bytes, with an optional binary unit, e.g. `512K`, `50M` or `1.5G`.
+fn parse_size(arg: &str) -> Result<u64> {
+    let arg = arg.trim();
+    let unit_start = arg
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(arg.len());
+    let (value, unit) = arg.split_at(unit_start);
+
+    let multiplier: u32 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => bail!("unknown size unit {unit}, expected K, M or G"),
+    };
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .context("expected a size, e.g. 50M")?;
+    if !value.is_finite() || value <= 0.0 {
+        bail!("expected a positive size, e.g. 50M");
+    }
+
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Ok((value * f64::from(multiplier)).ceil() as u64)
+}
+
+fn parse_field_value(arg: &str) -> Result<(MetaField, String)> {
+    let (field, value) = arg
+        .split_once('=')
+        .context("expected FIELD=VALUE, e.g. series=Berserk")?;
+
+    Ok((field.parse()?, value.to_string()))
+}
+
+fn default_filename(format: WriterFormat) -> String {
+    format!("{DEFAULT_FILE_STEM}.{}", format.extension())
+}
+
+fn reader_options(order: PageOrder, name_encoding: NameEncoding) -> ReaderOptions {
+    ReaderOptions::default()
+        .with_order(order.into())
+        .with_name_encoding(name_encoding)
+}
+
+/// Writer options for the given format, the title is taken from the output file stem. The cbz only
+/// options are rejected for epub output, before any page is written.
+fn writer_options(
+    format: WriterFormat,
+    path: &Path,
+    encoding: Option<EncodingOptions>,
+    job: &JobArgs,
+) -> Result<WriterOptions> {
+    if format == WriterFormat::Epub {
+        let cbz_only = [
+            ("--naming", job.naming.is_some()),
+            ("--compression", job.compression.is_some()),
+            ("--compression-level", job.compression_level.is_some()),
+            ("--sidecar", !job.sidecars.is_empty()),
+        ];
+        if let Some((flag, _)) = cbz_only.iter().find(|(_, set)| *set) {
+            bail!("{flag} only applies to cbz archives, it can't be used with --to epub");
+        }
+    }
+
+    let compression = job.compression.unwrap_or_default();
+    let mut opts = WriterOptions::new(format)
+        .with_no_clobber(job.no_clobber)
+        .with_naming(job.naming.clone().unwrap_or_default())
+        .with_compression(compression.into());
+    if let Some(encoding) = encoding {
+        opts = opts.with_encoding(encoding);
+    }
+    if let Some(level) = job.compression_level {
+        match WriterCompression::from(compression).levels() {
+            Some(levels) if levels.contains(&level) => {}
+            Some(levels) => bail!(
+                "--compression-level must be between {} and {} with this compression",
+                levels.start(),
+                levels.end()
+            ),
+            None => bail!("stored pages aren't compressed, --compression-level doesn't apply"),
+        }
+        opts = opts.with_compression_level(level);
+    }
+    for sidecar in &job.sidecars {
+        let buf =
+            fs::read(sidecar).with_context(|| format!("failed to read {}", sidecar.display()))?;
+        let name = sidecar.file_name().unwrap_or_default().to_string_lossy();
+        opts = opts.with_sidecar(name, buf);
+    }
+
+    Ok(match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) => opts.with_title(stem),
+        None => opts,
+    })
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use cli_table::{Cell, CellStruct, Style, Table};
+use reco::comic_info::{ReadingDirection, language_code};
+use reco_meta::Metadata;
+
+/// Prints the metadata of each archive, as a table or as a JSON array.
+pub fn print(metadata: &[Metadata], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(metadata)?);
+        return Ok(());
+    }
+
+    for metadata in metadata {
+        let table = rows(metadata).table().title(vec![
+            "Field".cell().bold(true),
+            metadata.path.display().cell().bold(true),
+        ]);
+        println!("{}", table.display()?);
+    }
+
+    Ok(())
+}
+
+fn rows(metadata: &Metadata) -> Vec<Vec<CellStruct>> {
+    let mut rows = vec![
+        ("Pages".to_string(), metadata.page_count.to_string()),
+        ("Dimensions".to_string(), dimensions(metadata)),
+    ];
+    let mut push = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            rows.push((name.to_string(), value));
+        }
+    };
+
+    push("Comment", metadata.comment.clone());
+
+    if let Some(comic_info) = &metadata.comic_info {
+        push("Title", comic_info.title.clone());
+        push("Series", comic_info.series.clone());
+        push("Number", comic_info.number.clone());
+        push("Volume", comic_info.volume.map(|volume| volume.to_string()));
+        push("Writer", comic_info.writer.clone());
+        push("Publisher", comic_info.publisher.clone());
+        push(
+            "Language",
+            comic_info
+                .language
+                .map(|language| language_code(language).to_string()),
+        );
+        push(
+            "Reading direction",
+            comic_info
+                .reading_direction
+                .map(|direction| match direction {
+                    ReadingDirection::LeftToRight => "ltr".to_string(),
+                    ReadingDirection::RightToLeft => "rtl".to_string(),
+                }),
+        );
+
+        for (name, content) in comic_info.others() {
+            push(name, Some(content.into_owned()));
+        }
+    }
+
+    rows.into_iter()
+        .map(|(name, value)| vec![name.cell(), value.cell()])
+        .collect()
+}
+
+/// The distinct page dimensions, the most common first, e.g. `800x1200 (12), 1600x1200 (2)`.
+fn dimensions(metadata: &Metadata) -> String {
+    let mut counts = BTreeMap::<_, usize>::new();
+    for page in &metadata.pages {
+        let dimensions = match (page.width, page.height) {
+            (Some(width), Some(height)) => format!("{width}x{height}"),
+            _ => "unknown".to_string(),
+        };
+        *counts.entry(dimensions).or_default() += 1;
+    }
+
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    counts
+        .into_iter()
+        .map(|(dimensions, count)| format!("{dimensions} ({count})"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,70 @@
+use std::{cell::Cell, io::IsTerminal, path::Path};
+
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use reco::Progress;
+
+const TEMPLATE: &str = "{elapsed_precise} [{wide_bar}] {pos}/{len} pages ({eta}) {msg}";
+
+/// A progress bar with the pages done, the bytes written and the current file, hidden when stdout
+/// isn't a terminal.
+pub struct PagesBar {
+    bar: ProgressBar,
+    bytes: Cell<u64>,
+    file: Cell<String>,
+}
+
+impl PagesBar {
+    pub fn new() -> Self {
+        let bar = if std::io::stdout().is_terminal() {
+            ProgressBar::new(0)
+        } else {
+            ProgressBar::hidden()
+        };
+        if let Ok(style) = ProgressStyle::with_template(TEMPLATE) {
+            bar.set_style(style.progress_chars("=> "));
+        }
+
+        Self {
+            bar,
+            bytes: Cell::new(0),
+            file: Cell::new(String::new()),
+        }
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+
+    fn update_message(&self) {
+        let file = self.file.take();
+        self.bar
+            .set_message(format!("{} {file}", HumanBytes(self.bytes.get())));
+        self.file.set(file);
+    }
+}
+
+impl Progress for PagesBar {
+    fn add_total(&self, pages: usize) {
+        self.bar.inc_length(pages as u64);
+    }
+
+    fn start_file(&self, path: &Path) {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        self.file.set(name.to_string_lossy().into_owned());
+        self.update_message();
+    }
+
+    fn page_done(&self, bytes: u64) {
+        self.bytes.set(self.bytes.get() + bytes);
+        // The total is only a guess for some sources (e.g. pdf)
+        if self
+            .bar
+            .length()
+            .is_some_and(|len| self.bar.position() >= len)
+        {
+            self.bar.inc_length(1);
+        }
+        self.bar.inc(1);
+        self.update_message();
+    }
+}
@@ -0,0 +1,78 @@
+use std::{path::Path, vec::IntoIter};
+
+use reco::{
+    BackendError, Reader as CbzReader, ReaderOpenError as CbzReaderOpenError,
+    reader::Options as ReaderOptions,
+};
+
+use crate::{Error, types::Page};
+
+/// A comic archive (cbz, cbr or cb7), whose images are copied as is.
+pub struct Archive {
+    reader: CbzReader,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("archive open error: {0}")]
+    Open(#[from] CbzReaderOpenError),
+
+    #[error("archive entry read error: {0}")]
+    EntryRead(#[from] BackendError),
+}
+
+impl Archive {
+    pub fn try_from_path(path: &Path, opts: ReaderOptions) -> Result<Self, Error> {
+        let reader = CbzReader::try_open_with_options(path, opts).map_err(ArchiveError::from)?;
+
+        Ok(Self { reader })
+    }
+}
+
+impl IntoIterator for Archive {
+    type Item = Result<Page, Error>;
+    type IntoIter = Pages;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let file_names = self
+            .reader
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        Pages {
+            reader: self.reader,
+            file_names: file_names.into_iter(),
+        }
+    }
+}
+
+/// The archive images, read lazily in the spine order.
+pub struct Pages {
+    reader: CbzReader,
+    file_names: IntoIter<String>,
+}
+
+impl Iterator for Pages {
+    type Item = Result<Page, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let file_name = self.file_names.next()?;
+        let ext = Path::new(&file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| format!(".{ext}"))
+            .unwrap_or_default();
+
+        Some(
+            self.reader
+                .read(&file_name)
+                .map(|buf| Page::Encoded { ext, buf })
+                .map_err(|err| ArchiveError::from(err).into()),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.file_names.size_hint()
+    }
+}
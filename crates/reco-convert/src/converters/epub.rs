@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::vec::IntoIter;
+
+use epub::doc::{DocError, EpubDoc};
+use tracing::debug;
+
+use crate::{Error, types::Page};
+
+pub struct Epub<R: Read + Seek = BufReader<File>> {
+    inner: EpubDoc<R>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EpubError {
+    #[error(transparent)]
+    Doc(#[from] DocError),
+
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+
+    #[error(transparent)]
+    IO(#[from] io::Error),
+
+    #[error("html parse error: {0}")]
+    Html(#[from] tl::ParseError),
+
+    #[error("resource not found: {0}")]
+    ResourceNotFound(String),
+}
+
+impl Epub {
+    pub fn try_from_path(path: &Path) -> Result<Self, Error> {
+        let epub = EpubDoc::new(path).map_err(EpubError::from)?;
+
+        Ok(Self { inner: epub })
+    }
+}
+
+impl<R: Read + Seek> Epub<R> {
+    /// The paths of the page images, in the spine (reading) order.
+    ///
+    /// Spine items can either be images themselves, or (x)html/svg documents embedding them.
+    fn image_paths(&mut self) -> Vec<Result<PathBuf, EpubError>> {
+        let mut seen = HashSet::new();
+        let mut paths = Vec::new();
+
+        let items = self
+            .inner
+            .spine
+            .iter()
+            .filter_map(|item| self.inner.resources.get(&item.idref))
+            .map(|resource| (resource.path.clone(), resource.mime.clone()))
+            .collect::<Vec<_>>();
+
+        for (path, mime) in items {
+            let document_paths = if mime.starts_with("image/") && mime != "image/svg+xml" {
+                vec![Ok(path)]
+            } else {
+                self.document_image_paths(&path)
+            };
+
+            for res in document_paths {
+                match res {
+                    Ok(path) if !seen.insert(path.clone()) => {
+                        debug!("{} already added, skipping", path.display());
+                    }
+                    res => paths.push(res),
+                }
+            }
+        }
+
+        paths
+    }
+
+    fn document_image_paths(&mut self, document_path: &Path) -> Vec<Result<PathBuf, EpubError>> {
+        let Some(content) = self.inner.get_resource_str_by_path(document_path) else {
+            return vec![Err(EpubError::ResourceNotFound(
+                document_path.display().to_string(),
+            ))];
+        };
+
+        let dom = match tl::parse(&content, tl::ParserOptions::default()) {
+            Ok(dom) => dom,
+            Err(err) => return vec![Err(err.into())],
+        };
+
+        let base = document_path.parent().unwrap_or_else(|| Path::new(""));
+        let mut paths = Vec::new();
+
+        for node in dom.nodes() {
+            let Some(tag) = node.as_tag() else {
+                continue;
+            };
+
+            let name = tag.name().as_utf8_str();
+            if !name.eq_ignore_ascii_case("img") && !name.eq_ignore_ascii_case("image") {
+                continue;
+            }
+
+            let attributes = tag.attributes();
+            let href = ["src", "xlink:href", "href"]
+                .into_iter()
+                .find_map(|key| attributes.get(key).flatten());
+
+            if let Some(href) = href {
+                paths.push(Ok(resolve_href(base, &href.as_utf8_str())));
+            }
+        }
+
+        if paths.is_empty() {
+            debug!("{} contains no images, skipping", document_path.display());
+        }
+
+        paths
+    }
+}
+
+impl<R: Read + Seek> IntoIterator for Epub<R> {
+    type Item = Result<Page, Error>;
+
+    type IntoIter = Pages<R>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let paths = self.image_paths();
+
+        Pages {
+            epub: self.inner,
+            paths: paths.into_iter(),
+        }
+    }
+}
+
+/// The book images, read lazily in the spine order.
+pub struct Pages<R: Read + Seek> {
+    epub: EpubDoc<R>,
+    paths: IntoIter<Result<PathBuf, EpubError>>,
+}
+
+impl<R: Read + Seek> Iterator for Pages<R> {
+    type Item = Result<Page, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let page = self
+            .paths
+            .next()?
+            .and_then(|path| read_page(&mut self.epub, &path));
+
+        Some(page.map_err(Into::into))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.paths.size_hint()
+    }
+}
+
+fn read_page<R: Read + Seek>(epub: &mut EpubDoc<R>, path: &Path) -> Result<Page, EpubError> {
+    let buf = epub
+        .get_resource_by_path(path)
+        .ok_or_else(|| EpubError::ResourceNotFound(path.display().to_string()))?;
+
+    Ok(Page::from_buf(buf))
+}
+
+/// Resolves a (percent encoded) href relative to the directory of the document referencing it.
+fn resolve_href(base: &Path, href: &str) -> PathBuf {
+    let href = href.split(['#', '?']).next().unwrap_or_default();
+    let href = percent_decode(href);
+    let mut path = PathBuf::new();
+
+    for component in base.join(href).components() {
+        match component {
+            Component::ParentDir => {
+                path.pop();
+            }
+            Component::Normal(part) => path.push(part),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+
+    path
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use reco::{
+        Writer,
+        writer::{Format as WriterFormat, Options as WriterOptions},
+    };
+
+    use super::*;
+
+    fn encode(format: ImageFormat) -> Result<Vec<u8>, image::ImageError> {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(8, 12));
+        let mut buf = Cursor::new(Vec::new());
+        img.write_to(&mut buf, format)?;
+
+        Ok(buf.into_inner())
+    }
+
+    #[test]
+    fn test_pages() -> Result<(), Box<dyn std::error::Error>> {
+        let jpeg = encode(ImageFormat::Jpeg)?;
+        let bmp = encode(ImageFormat::Bmp)?;
+        let opts = WriterOptions::new(WriterFormat::Epub);
+        let mut writer = Writer::with_options(Cursor::new(Vec::new()), opts);
+        writer.insert_buf(".jpg", &jpeg)?;
+        writer.insert_buf(".bmp", &bmp)?;
+        let mut buf = writer.finish()?;
+        buf.rewind()?;
+
+        let epub = Epub {
+            inner: EpubDoc::from_reader(buf)?,
+        };
+        let mut pages = epub.into_iter().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(pages.len(), 2);
+        assert!(matches!(&pages[0], Page::Encoded { ext, buf } if ext == ".jpg" && *buf == jpeg));
+        // The bmp page was converted when written
+        assert!(matches!(pages.pop(), Some(Page::Encoded { ext, .. }) if ext == ".png"));
+
+        // Other formats are decoded on the pipeline threads
+        let Page::Lazy(extract) = Page::from_buf(bmp) else {
+            panic!("bmp page not lazy");
+        };
+        assert!(matches!(extract()?, Page::Image(img) if img.width() == 8));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,871 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::vec::IntoIter;
+
+use tracing::debug;
+
+use crate::{Error, types::Page};
+
+/// Number of the EXTH record holding the index of the first KF8 record in combined files.
+const EXTH_KF8_BOUNDARY: u32 = 121;
+const EXTH_COVER_OFFSET: u32 = 201;
+const EXTH_THUMBNAIL_OFFSET: u32 = 202;
+
+const NO_INDEX: u32 = u32::MAX;
+
+/// Offsets, in the KF8 header record, of the fragment and skeleton index record numbers.
+const KF8_FRAGMENT_INDEX: usize = 0xF8;
+const KF8_SKELETON_INDEX: usize = 0xFC;
+
+/// Tags of the KF8 skeleton and fragment index entries.
+const TAG_FRAGMENT_COUNT: u8 = 1;
+const TAG_POSITION: u8 = 6;
+
+const COMPRESSION_NONE: u16 = 1;
+const COMPRESSION_PALMDOC: u16 = 2;
+
+/// A Mobipocket based e-book: MOBI (KF7), AZW3 (KF8), or combined KF7/KF8 files.
+pub struct Mobi {
+    db: PalmDb,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MobiError {
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+
+    #[error(transparent)]
+    IO(#[from] io::Error),
+
+    #[error("invalid palm database: {0}")]
+    InvalidDatabase(&'static str),
+
+    #[error("invalid mobi header: {0}")]
+    InvalidHeader(&'static str),
+
+    #[error("encrypted (drm) books are not supported")]
+    Encrypted,
+
+    #[error("unsupported text compression: {0}")]
+    UnsupportedCompression(u16),
+
+    #[error("record {0} not found")]
+    RecordNotFound(usize),
+
+    #[error("invalid index: {0}")]
+    InvalidIndex(&'static str),
+
+    #[error("the book has no images")]
+    NoImages,
+}
+
+impl Mobi {
+    pub fn try_from_path(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path).map_err(MobiError::from)?;
+        let db = PalmDb::try_new(data)?;
+
+        Ok(Self { db })
+    }
+
+    /// The indices of the image records, in reading order.
+    ///
+    /// The pages are ordered as they are referenced by the book text (`kindle:embed` for KF8,
+    /// `recindex` for KF7), which is the reading order of image only books. KF8 fragments are
+    /// first inserted in their skeletons (see [`kf8_parts`]), as the text doesn't store them in
+    /// reading order. When the text cannot be read, or doesn't reference any image, the image
+    /// records of the book are returned in database order.
+    fn image_records(&self) -> Result<Vec<usize>, MobiError> {
+        let k7 = MobiHeader::try_new(&self.db, 0)?;
+
+        if k7.encryption != 0 {
+            return Err(MobiError::Encrypted);
+        }
+
+        // Combined files hold a second (KF8) book after the boundary record
+        let k8 = k7
+            .exth_u32(EXTH_KF8_BOUNDARY)
+            .filter(|&boundary| boundary != NO_INDEX)
+            .and_then(
+                |boundary| match MobiHeader::try_new(&self.db, boundary as usize) {
+                    Ok(k8) => Some(k8),
+                    Err(err) => {
+                        debug!("kf8 header can't be read ({err}), using the kf7 one");
+                        None
+                    }
+                },
+            );
+        let header = k8.as_ref().unwrap_or(&k7);
+
+        let first_resource = header
+            .first_resource
+            .filter(|&index| self.is_image(index))
+            .or(k7.first_resource)
+            .ok_or(MobiError::InvalidHeader("no resource records"))?;
+
+        let thumbnail = k7
+            .exth_u32(EXTH_THUMBNAIL_OFFSET)
+            .map(|offset| first_resource + offset as usize);
+        let cover = k7
+            .exth_u32(EXTH_COVER_OFFSET)
+            .map(|offset| first_resource + offset as usize)
+            .filter(|&index| self.is_image(index));
+
+        let referenced = match header.text(&self.db) {
+            Ok(text) => text_references(&header.reading_text(&self.db, text))
+                .into_iter()
+                .map(|reference| first_resource + reference - 1)
+                .filter(|&index| self.is_image(index))
+                .collect::<Vec<_>>(),
+            Err(err) => {
+                debug!("text records can't be read ({err}), using the records order");
+                Vec::new()
+            }
+        };
+
+        if referenced.is_empty() {
+            // The KF7 resources of combined files end at the boundary record, the KF8 ones (read
+            // when the KF8 header is) at the end of the database
+            let end = k8
+                .as_ref()
+                .map(|k8| k8.start - 1)
+                .filter(|&end| end > first_resource)
+                .unwrap_or(self.db.len());
+            let indices = (first_resource..end)
+                .filter(|&index| Some(index) != thumbnail && self.is_image(index))
+                .collect::<Vec<_>>();
+            if indices.is_empty() {
+                return Err(MobiError::NoImages);
+            }
+
+            return Ok(indices);
+        }
+
+        let mut seen = HashSet::new();
+        let mut indices = Vec::with_capacity(referenced.len() + 1);
+
+        if let Some(cover) = cover
+            && !referenced.contains(&cover)
+        {
+            indices.push(cover);
+        }
+
+        for index in referenced {
+            if seen.insert(index) {
+                indices.push(index);
+            }
+        }
+
+        Ok(indices)
+    }
+
+    fn is_image(&self, index: usize) -> bool {
+        self.db.record(index).is_some_and(infer::is_image)
+    }
+}
+
+impl IntoIterator for Mobi {
+    type Item = Result<Page, Error>;
+
+    type IntoIter = Pages;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let indices = match self.image_records() {
+            Ok(indices) => indices.into_iter().map(Ok).collect::<Vec<_>>(),
+            Err(err) => vec![Err(err)],
+        };
+
+        Pages {
+            db: self.db,
+            indices: indices.into_iter(),
+        }
+    }
+}
+
+/// The book images, read lazily in the reading order.
+pub struct Pages {
+    db: PalmDb,
+    indices: IntoIter<Result<usize, MobiError>>,
+}
+
+impl Iterator for Pages {
+    type Item = Result<Page, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let page = self.indices.next()?.and_then(|index| {
+            let record = self
+                .db
+                .record(index)
+                .ok_or(MobiError::RecordNotFound(index))?;
+
+            Ok(Page::from_buf(record.to_vec()))
+        });
+
+        Some(page.map_err(Into::into))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+/// The raw palm database, see <https://wiki.mobileread.com/wiki/PDB>.
+struct PalmDb {
+    data: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PalmDb {
+    fn try_new(data: Vec<u8>) -> Result<Self, MobiError> {
+        if data.get(60..68) != Some(b"BOOKMOBI") {
+            return Err(MobiError::InvalidDatabase("not a mobi book"));
+        }
+
+        let count = read_u16(&data, 76).ok_or(MobiError::InvalidDatabase("truncated header"))?;
+        let mut offsets = Vec::with_capacity(count as usize);
+
+        for i in 0..count as usize {
+            let offset = read_u32(&data, 78 + i * 8)
+                .ok_or(MobiError::InvalidDatabase("truncated record list"))?
+                as usize;
+
+            if offset > data.len() || offsets.last().is_some_and(|&last| offset < last) {
+                return Err(MobiError::InvalidDatabase("invalid record offset"));
+            }
+
+            offsets.push(offset);
+        }
+
+        Ok(Self { data, offsets })
+    }
+
+    fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    fn record(&self, index: usize) -> Option<&[u8]> {
+        let start = *self.offsets.get(index)?;
+        let end = self
+            .offsets
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.data.len());
+
+        self.data.get(start..end)
+    }
+}
+
+/// The PalmDOC, MOBI and EXTH headers located in the first record of a book (or of the KF8 part).
+struct MobiHeader {
+    start: usize,
+    compression: u16,
+    text_record_count: usize,
+    encryption: u16,
+    first_resource: Option<usize>,
+    extra_flags: u16,
+    /// The KF8 skeleton and fragment index records, if any.
+    kf8_indexes: Option<(usize, usize)>,
+    exth: HashMap<u32, Vec<u8>>,
+}
+
+impl MobiHeader {
+    fn try_new(db: &PalmDb, start: usize) -> Result<Self, MobiError> {
+        let record = db.record(start).ok_or(MobiError::RecordNotFound(start))?;
+        let truncated = || MobiError::InvalidHeader("truncated header");
+
+        if record.get(16..20) != Some(b"MOBI") {
+            return Err(MobiError::InvalidHeader("missing mobi identifier"));
+        }
+
+        let compression = read_u16(record, 0).ok_or_else(truncated)?;
+        let text_record_count = read_u16(record, 8).ok_or_else(truncated)?;
+        let encryption = read_u16(record, 12).ok_or_else(truncated)?;
+        let header_length = read_u32(record, 20).ok_or_else(truncated)?;
+        let version = read_u32(record, 0x24).ok_or_else(truncated)?;
+        let index = |offset| {
+            read_u32(record, offset)
+                .filter(|&index| index != NO_INDEX && index != 0)
+                .map(|index| start + index as usize)
+        };
+        let first_resource = index(0x6C);
+        let kf8_indexes = if version >= 8 && 16 + header_length as usize >= KF8_SKELETON_INDEX + 4 {
+            index(KF8_SKELETON_INDEX).zip(index(KF8_FRAGMENT_INDEX))
+        } else {
+            None
+        };
+        let exth_flags = read_u32(record, 0x80).unwrap_or(0);
+        let extra_flags = if header_length >= 0xE4 {
+            read_u16(record, 0xF2).unwrap_or(0)
+        } else {
+            0
+        };
+
+        let exth = if exth_flags & 0x40 == 0 {
+            HashMap::new()
+        } else {
+            parse_exth(
+                record
+                    .get(16 + header_length as usize..)
+                    .unwrap_or_default(),
+            )
+        };
+
+        Ok(Self {
+            start,
+            compression,
+            text_record_count: text_record_count as usize,
+            encryption,
+            first_resource,
+            extra_flags,
+            kf8_indexes,
+            exth,
+        })
+    }
+
+    fn exth_u32(&self, kind: u32) -> Option<u32> {
+        self.exth.get(&kind).and_then(|data| read_u32(data, 0))
+    }
+
+    /// The decompressed text of the book, with the trailing entries of each record removed.
+    fn text(&self, db: &PalmDb) -> Result<Vec<u8>, MobiError> {
+        let mut text = Vec::new();
+
+        for index in self.start + 1..=self.start + self.text_record_count {
+            let record = db.record(index).ok_or(MobiError::RecordNotFound(index))?;
+            let record = trim_trailing_entries(record, self.extra_flags);
+
+            match self.compression {
+                COMPRESSION_NONE => text.extend_from_slice(record),
+                COMPRESSION_PALMDOC => palmdoc_decompress(record, &mut text),
+                compression => return Err(MobiError::UnsupportedCompression(compression)),
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// The text with the KF8 fragments inserted in their skeletons, the parts being followed by
+    /// the rest of the text (e.g. the CSS and SVG flows). The text is kept as is when the indexes
+    /// can't be read.
+    fn reading_text(&self, db: &PalmDb, text: Vec<u8>) -> Vec<u8> {
+        let Some((skeleton_index, fragment_index)) = self.kf8_indexes else {
+            return text;
+        };
+
+        let parts = read_index(db, skeleton_index).and_then(|skeletons| {
+            let fragments = read_index(db, fragment_index)?;
+            kf8_parts(&text, &skeletons, &fragments)
+        });
+
+        match parts {
+            Ok((mut parts, end)) => {
+                parts.extend_from_slice(text.get(end..).unwrap_or_default());
+                parts
+            }
+            Err(err) => {
+                debug!("kf8 indexes can't be read ({err}), using the text order");
+                text
+            }
+        }
+    }
+}
+
+/// An entry of an `INDX` index, its label and its tag values.
+#[derive(Debug)]
+struct IndexEntry {
+    label: Vec<u8>,
+    tags: HashMap<u8, Vec<usize>>,
+}
+
+impl IndexEntry {
+    fn tag(&self, tag: u8, index: usize) -> Result<usize, MobiError> {
+        self.tags
+            .get(&tag)
+            .and_then(|values| values.get(index))
+            .copied()
+            .ok_or(MobiError::InvalidIndex("missing tag"))
+    }
+}
+
+/// Reads the entries of the index whose header is this record, see
+/// <https://wiki.mobileread.com/wiki/MOBI#INDX_Header>.
+fn read_index(db: &PalmDb, index: usize) -> Result<Vec<IndexEntry>, MobiError> {
+    let invalid = || MobiError::InvalidIndex("truncated index");
+    let header = db.record(index).ok_or(MobiError::RecordNotFound(index))?;
+    if header.get(0..4) != Some(b"INDX") {
+        return Err(MobiError::InvalidIndex("missing indx identifier"));
+    }
+
+    // The tag table follows the header
+    let tagx = read_u32(header, 4).ok_or_else(invalid)? as usize;
+    let record_count = read_u32(header, 24).ok_or_else(invalid)? as usize;
+    if header.get(tagx..tagx + 4) != Some(b"TAGX") {
+        return Err(MobiError::InvalidIndex("missing tagx section"));
+    }
+    let tagx_len = read_u32(header, tagx + 4).ok_or_else(invalid)? as usize;
+    let control_bytes = read_u32(header, tagx + 8).ok_or_else(invalid)? as usize;
+    let tags = header
+        .get(tagx + 12..tagx + tagx_len)
+        .ok_or_else(invalid)?
+        .chunks_exact(4)
+        .map(|tag| (tag[0], tag[1], tag[2], tag[3]))
+        .collect::<Vec<_>>();
+
+    let mut entries = Vec::new();
+    for index in index + 1..=index + record_count {
+        let record = db.record(index).ok_or(MobiError::RecordNotFound(index))?;
+        let idxt = read_u32(record, 20).ok_or_else(invalid)? as usize;
+        let count = read_u32(record, 24).ok_or_else(invalid)? as usize;
+
+        let mut positions = (0..count)
+            .map(|i| read_u16(record, idxt + 4 + 2 * i).map(usize::from))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        positions.push(idxt);
+
+        for bounds in positions.windows(2) {
+            let entry = record.get(bounds[0]..bounds[1]).ok_or_else(invalid)?;
+            let (&label_len, entry) = entry.split_first().ok_or_else(invalid)?;
+            let label = entry.get(..usize::from(label_len)).ok_or_else(invalid)?;
+            let tags = read_tags(
+                &tags,
+                control_bytes,
+                entry.get(label.len()..).unwrap_or_default(),
+            )
+            .ok_or_else(invalid)?;
+
+            entries.push(IndexEntry {
+                label: label.to_vec(),
+                tags,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Reads the tag values of an index entry, following its control bytes. Tags are `(tag, values
+/// per entry, mask, end of control byte flag)`.
+fn read_tags(
+    tags: &[(u8, u8, u8, u8)],
+    control_bytes: usize,
+    entry: &[u8],
+) -> Option<HashMap<u8, Vec<usize>>> {
+    let mut offset = control_bytes;
+    let mut control_byte = 0;
+    // The values count, or their byte length, of the tags present
+    let mut present = Vec::new();
+
+    for &(tag, per_entry, mask, end) in tags {
+        if end & 1 == 1 {
+            control_byte += 1;
+            continue;
+        }
+
+        let value = entry.get(control_byte)? & mask;
+        if value == 0 {
+            continue;
+        }
+        if value == mask && mask.count_ones() > 1 {
+            let (len, consumed) = read_varint(entry.get(offset..)?)?;
+            offset += consumed;
+            present.push((tag, per_entry, None, Some(len)));
+        } else {
+            present.push((tag, per_entry, Some(value >> mask.trailing_zeros()), None));
+        }
+    }
+
+    let mut values = HashMap::new();
+    for (tag, per_entry, count, len) in present {
+        let mut tag_values = Vec::new();
+        if let Some(count) = count {
+            for _ in 0..usize::from(count) * usize::from(per_entry) {
+                let (value, consumed) = read_varint(entry.get(offset..)?)?;
+                offset += consumed;
+                tag_values.push(value);
+            }
+        } else {
+            let end = offset + len?;
+            while offset < end {
+                let (value, consumed) = read_varint(entry.get(offset..)?)?;
+                offset += consumed;
+                tag_values.push(value);
+            }
+        }
+        values.insert(tag, tag_values);
+    }
+
+    Some(values)
+}
+
+/// Reads a forward variable-width integer, its last byte having its high bit set. Returns the
+/// value and the number of bytes read.
+fn read_varint(data: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0usize;
+
+    for (i, &byte) in data.iter().enumerate().take(5) {
+        value = (value << 7) | usize::from(byte & 0x7F);
+        if byte & 0x80 != 0 {
+            return Some((value, i + 1));
+        }
+    }
+
+    None
+}
+
+/// The KF8 parts (the XHTML files, in reading order), rebuilt by inserting the fragments in their
+/// skeleton, and the text offset they end at. The text holds each skeleton followed by its
+/// fragments, see <https://wiki.mobileread.com/wiki/KF8>.
+fn kf8_parts(
+    text: &[u8],
+    skeletons: &[IndexEntry],
+    fragments: &[IndexEntry],
+) -> Result<(Vec<u8>, usize), MobiError> {
+    let out_of_bounds = || MobiError::InvalidIndex("position out of the text");
+    let mut parts = Vec::with_capacity(text.len());
+    let mut fragments = fragments.iter();
+    let mut end = 0;
+
+    for skeleton in skeletons {
+        let (position, len) = (
+            skeleton.tag(TAG_POSITION, 0)?,
+            skeleton.tag(TAG_POSITION, 1)?,
+        );
+        let mut part = text
+            .get(position..position + len)
+            .ok_or_else(out_of_bounds)?
+            .to_vec();
+        end = position + len;
+
+        for _ in 0..skeleton.tag(TAG_FRAGMENT_COUNT, 0)? {
+            let fragment = fragments
+                .next()
+                .ok_or(MobiError::InvalidIndex("missing fragment"))?;
+            let insert = std::str::from_utf8(&fragment.label)
+                .ok()
+                .and_then(|label| label.parse::<usize>().ok())
+                .ok_or(MobiError::InvalidIndex("invalid fragment position"))?;
+            let len = fragment.tag(TAG_POSITION, 1)?;
+
+            let slice = text.get(end..end + len).ok_or_else(out_of_bounds)?;
+            let at = insert.saturating_sub(position).min(part.len());
+            part.splice(at..at, slice.iter().copied());
+            end += len;
+        }
+
+        parts.extend(part);
+    }
+
+    Ok((parts, end))
+}
+
+fn parse_exth(data: &[u8]) -> HashMap<u32, Vec<u8>> {
+    let mut records = HashMap::new();
+
+    if data.get(0..4) != Some(b"EXTH") {
+        return records;
+    }
+
+    let count = read_u32(data, 8).unwrap_or(0);
+    let mut offset = 12;
+
+    for _ in 0..count {
+        let (Some(kind), Some(len)) = (read_u32(data, offset), read_u32(data, offset + 4)) else {
+            break;
+        };
+        let len = len as usize;
+
+        let Some(value) = data.get(offset + 8..offset + len.max(8)) else {
+            break;
+        };
+
+        records.entry(kind).or_insert_with(|| value.to_vec());
+        offset += len.max(8);
+    }
+
+    records
+}
+
+/// Resources indices (1-based) referenced by the text, in order of appearance.
+fn text_references(text: &[u8]) -> Vec<usize> {
+    const KINDLE_EMBED: &[u8] = b"kindle:embed:";
+    const RECINDEX: &[u8] = b"recindex=";
+
+    let mut references = Vec::new();
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+
+        if rest.starts_with(KINDLE_EMBED) {
+            i += KINDLE_EMBED.len();
+            let digits = text[i..]
+                .iter()
+                .take(4)
+                .map_while(|&c| char::from(c).to_digit(32))
+                .collect::<Vec<_>>();
+
+            if digits.len() == 4 {
+                let reference = digits.iter().fold(0, |acc, &digit| acc * 32 + digit);
+                references.push(reference as usize);
+            }
+        } else if rest.starts_with(RECINDEX) {
+            i += RECINDEX.len();
+            let digits = text[i..]
+                .iter()
+                .skip_while(|&&c| c == b'"' || c == b'\'')
+                .map_while(|&c| char::from(c).to_digit(10))
+                .fold(None, |acc: Option<usize>, digit| {
+                    Some(acc.unwrap_or(0) * 10 + digit as usize)
+                });
+
+            if let Some(reference) = digits {
+                references.push(reference);
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    references.retain(|&reference| reference > 0);
+    references
+}
+
+/// See <https://wiki.mobileread.com/wiki/MOBI#Variable-width_integers>.
+fn trim_trailing_entries(mut record: &[u8], extra_flags: u16) -> &[u8] {
+    let trailing_entries = (extra_flags >> 1).count_ones();
+
+    for _ in 0..trailing_entries {
+        let size = record.iter().rev().take(4).rev().fold(0, |size, &byte| {
+            let size = if byte & 0x80 == 0 { size } else { 0 };
+            (size << 7) | usize::from(byte & 0x7F)
+        });
+        record = &record[..record.len().saturating_sub(size)];
+    }
+
+    if extra_flags & 1 == 1
+        && let Some(&last) = record.last()
+    {
+        let size = usize::from(last & 0x3) + 1;
+        record = &record[..record.len().saturating_sub(size)];
+    }
+
+    record
+}
+
+/// See <https://wiki.mobileread.com/wiki/PalmDOC#PalmDOC_Compression>.
+fn palmdoc_decompress(input: &[u8], output: &mut Vec<u8>) {
+    let start = output.len();
+    let mut i = 0;
+
+    while i < input.len() {
+        let byte = input[i];
+        i += 1;
+
+        match byte {
+            0x01..=0x08 => {
+                let end = (i + usize::from(byte)).min(input.len());
+                output.extend_from_slice(&input[i..end]);
+                i = end;
+            }
+            0x00..=0x7F => output.push(byte),
+            0xC0..=0xFF => {
+                output.push(b' ');
+                output.push(byte ^ 0x80);
+            }
+            0x80..=0xBF => {
+                let Some(&next) = input.get(i) else {
+                    break;
+                };
+                i += 1;
+
+                let pair = (usize::from(byte) << 8 | usize::from(next)) & 0x3FFF;
+                let distance = pair >> 3;
+                let length = (pair & 0x7) + 3;
+
+                if distance == 0 || distance > output.len() - start {
+                    continue;
+                }
+
+                for _ in 0..length {
+                    output.push(output[output.len() - distance]);
+                }
+            }
+        }
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palmdoc_decompress() {
+        let mut output = b"kept".to_vec();
+        // A 2-byte literal run, literals, a space pair, a (distance 6, length 3) copy and an
+        // invalid one
+        palmdoc_decompress(b"\x02\x80\xffabcd\xc1\x80\x30\x80\x00", &mut output);
+        assert_eq!(output, b"kept\x80\xffabcd Aabc");
+    }
+
+    #[test]
+    fn test_trim_trailing_entries() {
+        // A multibyte entry (one byte and its size), then a 2-byte trailing entry
+        let record = b"text\xe3\x01Z\x82";
+        assert_eq!(trim_trailing_entries(record, 0b10), b"text\xe3\x01");
+        assert_eq!(trim_trailing_entries(record, 0b11), b"text");
+        assert_eq!(trim_trailing_entries(b"\x85", 0b10), b"");
+    }
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    fn mobi(records: Vec<Vec<u8>>) -> Mobi {
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        for record in records {
+            offsets.push(data.len());
+            data.extend(record);
+        }
+
+        Mobi {
+            db: PalmDb { data, offsets },
+        }
+    }
+
+    fn image(id: u8) -> Vec<u8> {
+        [PNG, &[id]].concat()
+    }
+
+    /// A header record with a single uncompressed (or `compression`) text record, its indexes
+    /// relative to it.
+    fn header(
+        compression: u16,
+        version: u32,
+        first_resource: u32,
+        kf8_indexes: Option<(u32, u32)>,
+        exth: &[(u32, u32)],
+    ) -> Vec<u8> {
+        let mut record = vec![0; 0x118];
+        record[0..2].copy_from_slice(&compression.to_be_bytes());
+        record[8..10].copy_from_slice(&1u16.to_be_bytes());
+        record[16..20].copy_from_slice(b"MOBI");
+        record[20..24].copy_from_slice(&0x108u32.to_be_bytes());
+        record[0x24..0x28].copy_from_slice(&version.to_be_bytes());
+        record[0x6C..0x70].copy_from_slice(&first_resource.to_be_bytes());
+        record[0x80..0x84].copy_from_slice(&0x40u32.to_be_bytes());
+        let (skeleton, fragment) = kf8_indexes.unwrap_or((NO_INDEX, NO_INDEX));
+        record[0xF8..0xFC].copy_from_slice(&fragment.to_be_bytes());
+        record[0xFC..0x100].copy_from_slice(&skeleton.to_be_bytes());
+
+        record.extend(b"EXTH");
+        record.extend((12 + 12 * exth.len() as u32).to_be_bytes());
+        record.extend((exth.len() as u32).to_be_bytes());
+        for (kind, value) in exth {
+            record.extend(kind.to_be_bytes());
+            record.extend(12u32.to_be_bytes());
+            record.extend(value.to_be_bytes());
+        }
+
+        record
+    }
+
+    /// The header and data records of an index of `(label, control byte, values)` entries, with
+    /// the fragment count and position tags.
+    fn index(entries: &[(&[u8], u8, &[u8])]) -> [Vec<u8>; 2] {
+        let tags: [[u8; 4]; 3] = [[1, 1, 0x01, 0], [6, 2, 0x10, 0], [0, 0, 0, 1]];
+        let mut header = b"INDX".to_vec();
+        header.extend(32u32.to_be_bytes());
+        header.resize(24, 0);
+        header.extend(1u32.to_be_bytes());
+        header.resize(32, 0);
+        header.extend(b"TAGX");
+        header.extend((12 + 4 * tags.len() as u32).to_be_bytes());
+        header.extend(1u32.to_be_bytes());
+        header.extend(tags.concat());
+
+        let mut data = vec![0; 28];
+        let mut positions = Vec::new();
+        for (label, control_byte, values) in entries {
+            positions.push(data.len() as u16);
+            data.push(label.len() as u8);
+            data.extend(*label);
+            data.push(*control_byte);
+            // Single byte variable-width integers
+            data.extend(values.iter().map(|value| value | 0x80));
+        }
+        let idxt = data.len() as u32;
+        data.extend(b"IDXT");
+        data.extend(positions.iter().flat_map(|position| position.to_be_bytes()));
+        data[0..4].copy_from_slice(b"INDX");
+        data[20..24].copy_from_slice(&idxt.to_be_bytes());
+        data[24..28].copy_from_slice(&(entries.len() as u32).to_be_bytes());
+
+        [header, data]
+    }
+
+    #[test]
+    fn test_kf8_fragments_order() {
+        // The second fragment is inserted before the first one, its image being the first page
+        let second = br#"<img src="kindle:embed:0002"/>"#;
+        let first = br#"<img src="kindle:embed:0001"/>"#;
+        let text = [b"<body></body>".as_slice(), second, first].concat();
+
+        let [skeleton_header, skeleton_data] = index(&[(b"SKEL0", 0x11, &[2, 0, 13])]);
+        let [fragment_header, fragment_data] = index(&[
+            (b"6", 0x10, &[13, second.len() as u8]),
+            (b"6", 0x10, &[43, first.len() as u8]),
+        ]);
+        let mobi = mobi(vec![
+            header(COMPRESSION_NONE, 8, 2, Some((4, 6)), &[]),
+            text,
+            image(1),
+            image(2),
+            skeleton_header,
+            skeleton_data,
+            fragment_header,
+            fragment_data,
+        ]);
+
+        assert_eq!(mobi.image_records().unwrap(), [2, 3]);
+    }
+
+    #[test]
+    fn test_combined_unsupported_compression() {
+        // The KF8 text can't be decompressed, the KF8 images follow the boundary
+        let mobi = mobi(vec![
+            header(COMPRESSION_NONE, 6, 2, None, &[(EXTH_KF8_BOUNDARY, 5)]),
+            b"<p>kf7</p>".to_vec(),
+            image(1),
+            image(2),
+            b"BOUNDARY".to_vec(),
+            header(17480, 8, 2, None, &[]),
+            b"compressed".to_vec(),
+            image(1),
+            image(2),
+        ]);
+
+        assert_eq!(mobi.image_records().unwrap(), [7, 8]);
+    }
+
+    #[test]
+    fn test_no_images() {
+        let mobi = mobi(vec![
+            header(COMPRESSION_NONE, 6, 2, None, &[]),
+            b"<p>text only</p>".to_vec(),
+            b"FLIS".to_vec(),
+        ]);
+
+        assert!(matches!(mobi.image_records(), Err(MobiError::NoImages)));
+    }
+}
@@ -1,3 +1,9 @@
+pub use archive::{Archive, ArchiveError};
+pub use epub::{Epub, EpubError};
+pub use mobi::{Mobi, MobiError};
 pub use pdf::{Pdf, PdfError};
 
+mod archive;
+mod epub;
+mod mobi;
 mod pdf;
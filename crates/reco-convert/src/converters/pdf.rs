@@ -1,16 +1,25 @@
-use std::io::{self, Cursor};
+use std::io;
 use std::path::Path;
-use std::vec::IntoIter;
+use std::sync::Arc;
 
-use image::{DynamicImage, ImageReader};
-use pdf::enc::StreamFilter;
-use pdf::file::{File as PdfFile, FileOptions as PdfFileOptions, NoLog, ObjectCache, StreamCache};
-use pdf::object::{Ref, Resolve, XObject};
+use pdf::file::{
+    File as PdfFile, FileOptions as PdfFileOptions, NoCache, NoLog, ObjectCache, SyncCache,
+};
 
 use crate::Error;
+use crate::types::Page;
+
+mod page;
+mod xobject;
 
 pub struct Pdf {
-    inner: PdfFile<Vec<u8>, ObjectCache, StreamCache, NoLog>,
+    inner: Arc<PdfFile<Vec<u8>, ObjectCache, NoCache, NoLog>>,
+}
+
+/// The pages of a [`Pdf`], rendered lazily on the pipeline threads.
+pub struct Pages {
+    pdf: Pdf,
+    index: u32,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -23,75 +32,69 @@ pub enum PdfError {
 
     #[error(transparent)]
     IO(#[from] io::Error),
+
+    #[error("jbig2 decode error: {0}")]
+    Jbig2(#[from] hayro_jbig2::DecodeError),
+
+    #[error("ccitt decode error: {0}")]
+    Ccitt(#[from] hayro_ccitt::DecodeError),
+
+    #[error("unsupported image filter: {0}")]
+    UnsupportedFilter(String),
+
+    #[error("unsupported image: {0}")]
+    UnsupportedImage(&'static str),
 }
 
 impl Pdf {
     pub fn try_from_path(path: &Path) -> Result<Self, Error> {
-        let pdf = PdfFileOptions::cached()
+        // Decoded streams are not cached, otherwise the images of every page rendered so far
+        // would stay in memory until the end of the conversion
+        let pdf = PdfFileOptions::uncached()
+            .cache(SyncCache::new(), NoCache)
             .open(path)
             .map_err(PdfError::from)?;
 
-        Ok(Self { inner: pdf })
+        Ok(Self {
+            inner: Arc::new(pdf),
+        })
     }
 }
 
 impl IntoIterator for Pdf {
-    type Item = Result<DynamicImage, Error>;
+    type Item = Result<Page, Error>;
 
-    type IntoIter = IntoIter<Self::Item>;
+    type IntoIter = Pages;
 
     fn into_iter(self) -> Self::IntoIter {
-        let mut imgs = Vec::new();
-        let resolver = self.inner.resolver();
-
-        for page in self.inner.pages() {
-            let page = match page {
-                Ok(page) => page,
-                Err(err) => {
-                    imgs.push(Err(PdfError::from(err).into()));
-                    continue;
-                }
-            };
-
-            let resources = match page.resources() {
-                Ok(resources) => resources,
-                Err(err) => {
-                    imgs.push(Err(PdfError::from(err).into()));
-                    continue;
-                }
-            };
-
-            for resource in resources.xobjects.values() {
-                if let Err(err) = decode_imgs(&resolver, resource, &mut imgs) {
-                    imgs.push(Err(err.into()));
-                    continue;
-                }
-            }
+        Pages {
+            pdf: self,
+            index: 0,
         }
-
-        imgs.into_iter()
     }
 }
 
-fn decode_imgs(
-    resolver: &impl Resolve,
-    resource: &Ref<XObject>,
-    imgs: &mut Vec<Result<DynamicImage, Error>>,
-) -> Result<(), PdfError> {
-    let resource = resolver.get(*resource)?;
+impl Iterator for Pages {
+    type Item = Result<Page, Error>;
 
-    if let XObject::Image(image) = &*resource {
-        let (image, filter) = image.raw_image_data(resolver)?;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.pdf.inner.num_pages() {
+            return None;
+        }
 
-        if let Some(StreamFilter::DCTDecode(_)) = filter {
-            let img_reader = ImageReader::new(Cursor::new(image));
-            let img = img_reader.with_guessed_format()?.decode()?;
+        let (file, index) = (Arc::clone(&self.pdf.inner), self.index);
+        self.index += 1;
 
-            imgs.push(Ok(img));
+        Some(Ok(Page::Lazy(Box::new(move || {
+            let page = file.get_page(index).map_err(PdfError::from)?;
 
-            return Ok(());
-        }
+            Ok(page::render(&file.resolver(), &page)?)
+        }))))
     }
 
-    Ok(())
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.pdf.inner.num_pages().saturating_sub(self.index) as usize;
+
+        (remaining, Some(remaining))
+    }
 }
@@ -0,0 +1,350 @@
+//! Composition of a page from the image XObjects its content stream draws.
+//!
+//! Only images are painted, text and vector graphics are ignored, which is enough for scanned
+//! documents and comics whose pages are made of (possibly tiled or overlaid) images.
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage, imageops, imageops::FilterType};
+use pdf::content::{Matrix, Op};
+use pdf::object::{ImageXObject, Object, Page as PdfPage, Resolve, Resources, XObject};
+use tracing::debug;
+
+use super::{PdfError, xobject};
+use crate::types::Page;
+
+/// Form XObjects can reference each other, this prevents infinite recursions.
+const MAX_FORM_DEPTH: usize = 16;
+
+/// Bounds the size of a page raster, whatever the resolution of its images.
+const MAX_DIMENSION: f32 = 16384.0;
+
+/// Tolerance, in points, when checking whether an image covers the whole page.
+const TOLERANCE: f32 = 1.0;
+
+/// An image and the transformation mapping the unit square to its location on the page.
+struct Draw {
+    image: ImageXObject,
+    ctm: Matrix,
+}
+
+/// Renders the images of a page into a single raster covering its media box.
+///
+/// The raster resolution is the highest resolution of the images drawn on the page (but at least
+/// one pixel per point), so full page scans keep their original size. A page made of a single
+/// JPEG image is not rendered at all, its data is used as is.
+pub(super) fn render(resolver: &impl Resolve, page: &PdfPage) -> Result<Page, PdfError> {
+    let media_box = page.media_box()?;
+    let (left, top) = (
+        media_box.left.min(media_box.right),
+        media_box.top.max(media_box.bottom),
+    );
+    let (width, height) = (
+        (media_box.right - media_box.left).abs().max(1.0),
+        (media_box.top - media_box.bottom).abs().max(1.0),
+    );
+
+    let mut draws = Vec::new();
+    if let Some(contents) = &page.contents {
+        let ops = contents.operations(resolver)?;
+        collect_draws(
+            resolver,
+            &ops,
+            page.resources()?,
+            Matrix::default(),
+            0,
+            &mut draws,
+        )?;
+    }
+
+    if let [draw] = draws.as_slice()
+        && covers(&draw.ctm, left, top, width, height)
+    {
+        if page.rotate.rem_euclid(360) == 0
+            && let Some(buf) = xobject::jpeg_data(resolver, &draw.image)?
+        {
+            return Ok(Page::Encoded {
+                ext: ".jpeg".to_string(),
+                buf: buf.to_vec(),
+            });
+        }
+
+        let img = xobject::decode_img(resolver, &draw.image)?;
+        return Ok(Page::Image(rotate(xobject::flatten(img), page.rotate)));
+    }
+
+    let scale = draws
+        .iter()
+        .map(|draw| {
+            let scale_x = draw.image.width as f32 / draw.ctm.a.hypot(draw.ctm.b);
+            let scale_y = draw.image.height as f32 / draw.ctm.c.hypot(draw.ctm.d);
+            scale_x.max(scale_y)
+        })
+        .filter(|scale| scale.is_finite())
+        .fold(1.0, f32::max)
+        .min(MAX_DIMENSION / width.max(height));
+
+    let mut canvas = RgbaImage::from_pixel(
+        (width * scale).round().max(1.0) as u32,
+        (height * scale).round().max(1.0) as u32,
+        Rgba([255, 255, 255, 255]),
+    );
+
+    for draw in draws {
+        // Maps the unit square to the canvas pixels, pdf user space has its y axis pointing up
+        let ctm = &draw.ctm;
+        let device = Matrix {
+            a: ctm.a * scale,
+            b: -ctm.b * scale,
+            c: ctm.c * scale,
+            d: -ctm.d * scale,
+            e: (ctm.e - left) * scale,
+            f: (top - ctm.f) * scale,
+        };
+        let img = xobject::decode_img(resolver, &draw.image)?;
+        paint(&mut canvas, &img, &device);
+    }
+
+    let img = DynamicImage::ImageRgba8(canvas);
+
+    Ok(Page::Image(rotate(xobject::flatten(img), page.rotate)))
+}
+
+fn collect_draws(
+    resolver: &impl Resolve,
+    ops: &[Op],
+    resources: &Resources,
+    ctm: Matrix,
+    depth: usize,
+    draws: &mut Vec<Draw>,
+) -> Result<(), PdfError> {
+    let mut stack = Vec::new();
+    let mut ctm = ctm;
+
+    for op in ops {
+        match op {
+            Op::Save => stack.push(ctm),
+            Op::Restore => ctm = stack.pop().unwrap_or(ctm),
+            Op::Transform { matrix } => ctm = concat(matrix, &ctm),
+            Op::InlineImage { image } => draws.push(Draw {
+                image: (**image).clone(),
+                ctm,
+            }),
+            Op::XObject { name } => {
+                let Some(reference) = resources.xobjects.get(name) else {
+                    debug!("xobject {name} not found in the resources, skipping");
+                    continue;
+                };
+
+                match &*resolver.get(*reference)? {
+                    XObject::Image(image) => draws.push(Draw {
+                        image: image.clone(),
+                        ctm,
+                    }),
+                    XObject::Form(form) if depth < MAX_FORM_DEPTH => {
+                        let dict = form.dict();
+                        let matrix = match &dict.matrix {
+                            Some(matrix) => Matrix::from_primitive(matrix.clone(), resolver)?,
+                            None => Matrix::default(),
+                        };
+                        let form_resources = dict.resources.as_deref().unwrap_or(resources);
+                        let form_ops = form.operations(resolver)?;
+
+                        collect_draws(
+                            resolver,
+                            &form_ops,
+                            form_resources,
+                            concat(&matrix, &ctm),
+                            depth + 1,
+                            draws,
+                        )?;
+                    }
+                    XObject::Form(_) => debug!("form xobject {name} nested too deeply, skipping"),
+                    XObject::Postscript(_) => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The transformation applying `first` then `then`.
+fn concat(first: &Matrix, then: &Matrix) -> Matrix {
+    Matrix {
+        a: first.a * then.a + first.b * then.c,
+        b: first.a * then.b + first.b * then.d,
+        c: first.c * then.a + first.d * then.c,
+        d: first.c * then.b + first.d * then.d,
+        e: first.e * then.a + first.f * then.c + then.e,
+        f: first.e * then.b + first.f * then.d + then.f,
+    }
+}
+
+/// Whether the image is drawn upright and fills the whole media box.
+fn covers(ctm: &Matrix, left: f32, top: f32, width: f32, height: f32) -> bool {
+    ctm.b.abs() < f32::EPSILON
+        && ctm.c.abs() < f32::EPSILON
+        && (ctm.a - width).abs() < TOLERANCE
+        && (ctm.d - height).abs() < TOLERANCE
+        && (ctm.e - left).abs() < TOLERANCE
+        && (ctm.f + ctm.d - top).abs() < TOLERANCE
+}
+
+/// Paints the image, whose unit square is mapped to the canvas by `device`.
+fn paint(canvas: &mut RgbaImage, img: &DynamicImage, device: &Matrix) {
+    let (img_width, img_height) = img.dimensions();
+
+    // Image samples start at the top left corner, i.e. at the (0, 1) corner of the unit square
+    if device.b.abs() < f32::EPSILON && device.c.abs() < f32::EPSILON {
+        let (x0, x1) = (device.e, device.e + device.a);
+        let (y0, y1) = (device.f + device.d, device.f);
+        let width = (x1 - x0).abs().round() as u32;
+        let height = (y1 - y0).abs().round() as u32;
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        // Only the part of the image inside the canvas is resized, images can be drawn (much)
+        // larger than the page
+        let (x, y) = (x0.min(x1).round() as i64, y0.min(y1).round() as i64);
+        let (canvas_width, canvas_height) = canvas.dimensions();
+        let (left, top) = (x.max(0), y.max(0));
+        let right = x
+            .saturating_add(i64::from(width))
+            .min(i64::from(canvas_width));
+        let bottom = y
+            .saturating_add(i64::from(height))
+            .min(i64::from(canvas_height));
+        if right <= left || bottom <= top {
+            return;
+        }
+
+        let mut img = img.to_rgba8();
+        if x1 < x0 {
+            imageops::flip_horizontal_in_place(&mut img);
+        }
+        if y1 < y0 {
+            imageops::flip_vertical_in_place(&mut img);
+        }
+
+        let source = |start: i64, end: i64, offset: i64, size: u32, img_size: u32| {
+            let scale = f64::from(img_size) / f64::from(size);
+            let start = ((start - offset) as f64 * scale).floor() as u32;
+            let end = ((end - offset) as f64 * scale).ceil() as u32;
+            let start = start.min(img_size - 1);
+            (start, end.clamp(start + 1, img_size) - start)
+        };
+        let (src_x, src_width) = source(left, right, x, width, img_width);
+        let (src_y, src_height) = source(top, bottom, y, height, img_height);
+        let visible = imageops::crop_imm(&img, src_x, src_y, src_width, src_height).to_image();
+
+        let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+        let resized = if visible.dimensions() == (width, height) {
+            visible
+        } else {
+            imageops::resize(&visible, width, height, FilterType::Triangle)
+        };
+
+        imageops::overlay(canvas, &resized, left, top);
+        return;
+    }
+
+    // Rotated or skewed images are sampled through the inverse transformation
+    let det = device.a * device.d - device.b * device.c;
+    if det.abs() < f32::EPSILON {
+        return;
+    }
+
+    let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(u, v)| {
+        (
+            device.a * u + device.c * v + device.e,
+            device.b * u + device.d * v + device.f,
+        )
+    });
+    let (canvas_width, canvas_height) = canvas.dimensions();
+    let min_x = corners
+        .iter()
+        .map(|c| c.0)
+        .fold(f32::INFINITY, f32::min)
+        .max(0.0) as u32;
+    let max_x = corners
+        .iter()
+        .map(|c| c.0)
+        .fold(f32::NEG_INFINITY, f32::max)
+        .ceil()
+        .max(0.0) as u32;
+    let min_y = corners
+        .iter()
+        .map(|c| c.1)
+        .fold(f32::INFINITY, f32::min)
+        .max(0.0) as u32;
+    let max_y = corners
+        .iter()
+        .map(|c| c.1)
+        .fold(f32::NEG_INFINITY, f32::max)
+        .ceil()
+        .max(0.0) as u32;
+
+    let img = img.to_rgba8();
+
+    for y in min_y..max_y.min(canvas_height) {
+        for x in min_x..max_x.min(canvas_width) {
+            let (dx, dy) = (x as f32 + 0.5 - device.e, y as f32 + 0.5 - device.f);
+            let u = (device.d * dx - device.c * dy) / det;
+            let v = (device.a * dy - device.b * dx) / det;
+            if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                continue;
+            }
+
+            let sample = img.get_pixel(
+                ((u * img_width as f32) as u32).min(img_width - 1),
+                (((1.0 - v) * img_height as f32) as u32).min(img_height - 1),
+            );
+            blend(canvas.get_pixel_mut(x, y), sample);
+        }
+    }
+}
+
+fn blend(dst: &mut Rgba<u8>, src: &Rgba<u8>) {
+    let alpha = u16::from(src[3]);
+    for i in 0..3 {
+        let value = u16::from(src[i]) * alpha + u16::from(dst[i]) * (255 - alpha);
+        dst[i] = (value / 255) as u8;
+    }
+}
+
+/// Applies the page `/Rotate` entry, the number of degrees the page is rotated clockwise by.
+fn rotate(img: DynamicImage, degrees: i32) -> DynamicImage {
+    match degrees.rem_euclid(360) {
+        90 => img.rotate90(),
+        180 => img.rotate180(),
+        270 => img.rotate270(),
+        _ => img,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::GrayImage;
+
+    use super::*;
+
+    #[test]
+    fn test_paint_clipped() {
+        // The left half of the image is black, the right one white
+        let img = DynamicImage::ImageLuma8(GrayImage::from_fn(2, 1, |x, _| [255 * x as u8].into()));
+        let mut canvas = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255]));
+
+        // Drawn a billion pixels wide, only its black part covers the canvas
+        let device = Matrix {
+            a: 1e9,
+            b: 0.0,
+            c: 0.0,
+            d: -4.0,
+            e: -2.0,
+            f: 4.0,
+        };
+        paint(&mut canvas, &img, &device);
+        assert!(canvas.pixels().all(|pixel| *pixel == Rgba([0, 0, 0, 255])));
+    }
+}
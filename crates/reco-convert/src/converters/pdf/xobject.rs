@@ -0,0 +1,786 @@
+//! Decoding of image XObjects, whatever their filter, colour space, or bit depth.
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+use image::{
+    DynamicImage, GenericImageView, GrayAlphaImage, GrayImage, ImageFormat, ImageReader, RgbImage,
+    RgbaImage, imageops::FilterType,
+};
+use pdf::enc::{CCITTFaxDecodeParams, StreamFilter, flate_decode};
+use pdf::object::{ColorSpace, ImageXObject, Object, Resolve};
+use pdf::primitive::{Dictionary, Primitive};
+
+use super::PdfError;
+
+/// Bounds the memory used by the samples of a single image (1 GiB), whatever the dimensions its
+/// header claims.
+const MAX_SAMPLES: usize = 1 << 29;
+
+/// The number of samples of an image, checked as its dimensions come from the file.
+fn sample_count(width: u32, height: u32, components: usize) -> Result<usize, PdfError> {
+    if width == 0 || height == 0 {
+        return Err(PdfError::UnsupportedImage("empty image"));
+    }
+
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|count| count.checked_mul(components))
+        .filter(|&count| count <= MAX_SAMPLES)
+        .ok_or(PdfError::UnsupportedImage("image too large"))
+}
+
+/// The image samples, one value per component (up to 16 bits).
+struct Samples {
+    width: u32,
+    height: u32,
+    bits_per_component: u32,
+    values: Vec<u16>,
+}
+
+impl Samples {
+    fn max(&self) -> f32 {
+        ((1u32 << self.bits_per_component) - 1) as f32
+    }
+
+    /// Unpacks the raw (byte aligned rows) data of an image.
+    fn unpack(
+        data: &[u8],
+        width: u32,
+        height: u32,
+        components: usize,
+        bpc: u32,
+    ) -> Result<Self, PdfError> {
+        if !matches!(bpc, 1 | 2 | 4 | 8 | 16) {
+            return Err(PdfError::UnsupportedImage("unsupported bits per component"));
+        }
+
+        let mut values = Vec::with_capacity(sample_count(width, height, components)?);
+        let per_row = width as usize * components;
+        let stride = (per_row * bpc as usize).div_ceil(8);
+
+        for y in 0..height as usize {
+            let row = data.get(y * stride..).unwrap_or_default();
+
+            for i in 0..per_row {
+                let value = match bpc {
+                    16 => row
+                        .get(i * 2..i * 2 + 2)
+                        .map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]])),
+                    8 => row.get(i).copied().map_or(0, u16::from),
+                    _ => {
+                        let bit = i * bpc as usize;
+                        let byte = row.get(bit / 8).copied().unwrap_or_default();
+                        let shift = 8 - bpc as usize - bit % 8;
+                        u16::from((byte >> shift) & ((1 << bpc) - 1) as u8)
+                    }
+                };
+                values.push(value);
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            bits_per_component: bpc,
+            values,
+        })
+    }
+}
+
+/// Bi-level samples produced by the JBIG2 and CCITT decoders, `black` is the value of black pixels.
+struct BilevelSink {
+    black: u16,
+    values: Vec<u16>,
+}
+
+impl BilevelSink {
+    fn push(&mut self, black: bool, count: usize) {
+        let value = if black { self.black } else { 1 - self.black };
+        self.values.extend(std::iter::repeat_n(value, count));
+    }
+}
+
+impl hayro_jbig2::Decoder for BilevelSink {
+    fn push_pixel(&mut self, black: bool) {
+        self.push(black, 1);
+    }
+
+    fn push_pixel_chunk(&mut self, black: bool, chunk_count: u32) {
+        self.push(black, chunk_count as usize * 8);
+    }
+
+    fn next_line(&mut self) {}
+}
+
+impl hayro_ccitt::Decoder for BilevelSink {
+    fn push_pixels(&mut self, white: bool, count: u32) {
+        self.push(!white, count as usize);
+    }
+
+    fn next_line(&mut self) {}
+}
+
+enum Decoded {
+    Samples(Samples),
+    Image(DynamicImage),
+}
+
+/// Decodes an image XObject into a luma or rgb image, with an alpha channel when it is masked.
+pub(super) fn decode_img(
+    resolver: &impl Resolve,
+    image: &ImageXObject,
+) -> Result<DynamicImage, PdfError> {
+    let decoded = decode_data(resolver, image)?;
+
+    if image.image_mask {
+        let Decoded::Samples(samples) = decoded else {
+            return Err(PdfError::UnsupportedImage("image mask must be bi-level"));
+        };
+        return Ok(stencil(&samples, image.decode.as_deref()));
+    }
+
+    let color_space = image
+        .color_space
+        .as_ref()
+        .unwrap_or(&ColorSpace::DeviceGray);
+
+    let (img, alpha) = match decoded {
+        Decoded::Samples(samples) => {
+            let alpha = match image.mask.as_ref() {
+                Some(Primitive::Array(ranges)) => Some(color_key_mask(&samples, ranges, resolver)),
+                _ => None,
+            };
+            let decode = decode_ranges(color_space, image.decode.as_deref(), &samples);
+            (to_image(&samples, color_space, &decode), alpha)
+        }
+        Decoded::Image(img) if is_inverted(image.decode.as_deref()) => {
+            let mut img = img;
+            img.invert();
+            (img, None)
+        }
+        Decoded::Image(img) => (img, None),
+    };
+
+    let alpha = match (alpha, &image.smask, &image.mask) {
+        (Some(alpha), _, _) => Some(alpha),
+        (None, Some(smask), _) => {
+            let smask = ImageXObject {
+                inner: (*resolver.get(*smask)?).clone(),
+            };
+            Some(soft_mask(resolver, &smask)?)
+        }
+        (None, None, Some(mask @ Primitive::Reference(_))) => {
+            let mask = ImageXObject::from_primitive(mask.clone(), resolver)?;
+            Some(explicit_mask(resolver, &mask)?)
+        }
+        _ => None,
+    };
+
+    Ok(match alpha {
+        Some(alpha) => with_alpha(img, &alpha),
+        None => img,
+    })
+}
+
+/// The raw JPEG data of the image, if it can be used as is, i.e. if decoding it only takes a
+/// JPEG decoder, without colour conversion or masking.
+pub(super) fn jpeg_data(
+    resolver: &impl Resolve,
+    image: &ImageXObject,
+) -> Result<Option<Arc<[u8]>>, PdfError> {
+    let plain = !image.image_mask
+        && image.mask.is_none()
+        && image.smask.is_none()
+        && image.decode.is_none()
+        && image
+            .color_space
+            .as_ref()
+            .is_none_or(|color_space| is_gray(color_space) || is_rgb(color_space));
+    if !plain {
+        return Ok(None);
+    }
+
+    let (data, filter) = image.raw_image_data(resolver)?;
+    if !matches!(filter, Some(StreamFilter::DCTDecode(_))) {
+        return Ok(None);
+    }
+
+    Ok(Some(data))
+}
+
+fn decode_data(resolver: &impl Resolve, image: &ImageXObject) -> Result<Decoded, PdfError> {
+    let (data, filter) = image.raw_image_data(resolver)?;
+    let (width, height) = (image.width, image.height);
+    let bpc = if image.image_mask {
+        1
+    } else {
+        u32::try_from(image.bits_per_component.unwrap_or(8)).unwrap_or_default()
+    };
+    let components = if image.image_mask {
+        1
+    } else {
+        image.color_space.as_ref().map_or(1, components)
+    };
+
+    let decoded = match filter {
+        None => Decoded::Samples(Samples::unpack(&data, width, height, components, bpc)?),
+        Some(StreamFilter::FlateDecode(params)) => {
+            let data = flate_decode(&data, params)?;
+            Decoded::Samples(Samples::unpack(&data, width, height, components, bpc)?)
+        }
+        Some(StreamFilter::DCTDecode(_)) => {
+            let reader = ImageReader::with_format(Cursor::new(&*data), ImageFormat::Jpeg);
+            Decoded::Image(reader.decode()?)
+        }
+        Some(StreamFilter::JPXDecode) => {
+            let decoder = hayro_jpeg2000::integration::Jp2Decoder::new(Cursor::new(&*data))?;
+            Decoded::Image(DynamicImage::from_decoder(decoder)?)
+        }
+        Some(StreamFilter::JBIG2Decode(params)) => {
+            let globals = params
+                .globals
+                .as_ref()
+                .map(|globals| globals.data(resolver))
+                .transpose()?;
+            Decoded::Samples(jbig2_decode(&data, globals.as_deref())?)
+        }
+        Some(StreamFilter::CCITTFaxDecode(params)) => {
+            Decoded::Samples(ccitt_decode(&data, params, width, height)?)
+        }
+        Some(filter) => return Err(PdfError::UnsupportedFilter(format!("{filter:?}"))),
+    };
+
+    Ok(decoded)
+}
+
+fn jbig2_decode(data: &[u8], globals: Option<&[u8]>) -> Result<Samples, PdfError> {
+    let jbig2 = hayro_jbig2::Image::new_embedded(data, globals)?;
+    let (width, height) = (jbig2.width(), jbig2.height());
+    let count = sample_count(width, height, 1)?;
+
+    // Decoded JBIG2 data uses 1 for white, as the other bi-level filters by default
+    let mut sink = BilevelSink {
+        black: 0,
+        values: Vec::with_capacity(count),
+    };
+    jbig2.decode(&mut sink)?;
+
+    Ok(bilevel(sink, width, height, count))
+}
+
+fn ccitt_decode(
+    data: &[u8],
+    params: &CCITTFaxDecodeParams,
+    width: u32,
+    height: u32,
+) -> Result<Samples, PdfError> {
+    let encoding = match params.k {
+        k if k < 0 => hayro_ccitt::EncodingMode::Group4,
+        0 => hayro_ccitt::EncodingMode::Group3_1D,
+        k => hayro_ccitt::EncodingMode::Group3_2D {
+            k: k.unsigned_abs(),
+        },
+    };
+    let rows = if params.rows == 0 {
+        height
+    } else {
+        params.rows
+    };
+    let settings = hayro_ccitt::DecodeSettings {
+        columns: params.columns,
+        rows,
+        end_of_block: params.end_of_block,
+        end_of_line: params.end_of_line,
+        rows_are_byte_aligned: params.encoded_byte_align,
+        encoding,
+        invert_black: false,
+    };
+
+    let count = sample_count(params.columns, rows, 1)?;
+    let mut sink = BilevelSink {
+        black: u16::from(params.black_is_1),
+        values: Vec::with_capacity(count),
+    };
+    let mut ctx = hayro_ccitt::DecoderContext::new(settings);
+
+    // Truncated streams still produce the rows decoded so far
+    if let Err(err) = hayro_ccitt::decode(data, &mut sink, &mut ctx)
+        && sink.values.is_empty()
+    {
+        return Err(err.into());
+    }
+
+    let mut samples = bilevel(sink, params.columns, rows, count);
+    if samples.width != width {
+        sample_count(width, height, 1)?;
+        let columns = samples.width as usize;
+        samples = Samples {
+            values: (0..height as usize)
+                .flat_map(|y| (0..width as usize).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let index = y * columns + x.min(columns - 1);
+                    samples.values.get(index).copied().unwrap_or(1)
+                })
+                .collect(),
+            width,
+            height,
+            bits_per_component: 1,
+        };
+    }
+
+    Ok(samples)
+}
+
+/// Samples of `count` bi-level values, padded with white if the decoded data was truncated.
+fn bilevel(sink: BilevelSink, width: u32, height: u32, count: usize) -> Samples {
+    let mut values = sink.values;
+    values.resize(count, 1 - sink.black);
+
+    Samples {
+        width,
+        height,
+        bits_per_component: 1,
+        values,
+    }
+}
+
+fn components(color_space: &ColorSpace) -> usize {
+    match color_space {
+        ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) => 3,
+        ColorSpace::DeviceCMYK | ColorSpace::CalCMYK(_) => 4,
+        ColorSpace::DeviceN { names, .. } => names.len().max(1),
+        ColorSpace::Icc(icc) => icc.info.info.components as usize,
+        ColorSpace::Other(array) if is_lab(array) => 3,
+        ColorSpace::DeviceGray
+        | ColorSpace::CalGray(_)
+        | ColorSpace::Indexed(..)
+        | ColorSpace::Separation(..)
+        | ColorSpace::Pattern
+        | ColorSpace::Named(_)
+        | ColorSpace::Other(_) => 1,
+    }
+}
+
+fn is_lab(array: &[Primitive]) -> bool {
+    array
+        .first()
+        .and_then(|name| name.as_name().ok())
+        .is_some_and(|name| name == "Lab")
+}
+
+fn is_gray(color_space: &ColorSpace) -> bool {
+    match color_space {
+        ColorSpace::DeviceGray | ColorSpace::CalGray(_) => true,
+        ColorSpace::Icc(icc) => icc.info.info.components == 1,
+        _ => false,
+    }
+}
+
+fn is_rgb(color_space: &ColorSpace) -> bool {
+    match color_space {
+        ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) => true,
+        ColorSpace::Icc(icc) => icc.info.info.components == 3,
+        _ => false,
+    }
+}
+
+fn is_inverted(decode: Option<&[f32]>) -> bool {
+    matches!(decode, Some([min, max, ..]) if min > max)
+}
+
+/// The `[min, max]` range each component's samples are mapped to.
+fn decode_ranges(
+    color_space: &ColorSpace,
+    decode: Option<&[f32]>,
+    samples: &Samples,
+) -> Vec<(f32, f32)> {
+    let count = components(color_space);
+
+    if let Some(decode) = decode
+        && decode.len() >= count * 2
+    {
+        return decode.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+    }
+
+    match color_space {
+        ColorSpace::Indexed(..) => vec![(0.0, samples.max())],
+        ColorSpace::Other(array) if is_lab(array) => {
+            let range = lab_dict(array)
+                .and_then(|dict| dict.get("Range").cloned())
+                .and_then(|range| range.into_array().ok())
+                .map(|range| {
+                    range
+                        .iter()
+                        .filter_map(|value| value.as_number().ok())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|range| range.len() == 4)
+                .unwrap_or_else(|| vec![-100.0, 100.0, -100.0, 100.0]);
+            vec![(0.0, 100.0), (range[0], range[1]), (range[2], range[3])]
+        }
+        _ => vec![(0.0, 1.0); count],
+    }
+}
+
+fn lab_dict(array: &[Primitive]) -> Option<&Dictionary> {
+    match array.get(1)? {
+        Primitive::Dictionary(dict) => Some(dict),
+        _ => None,
+    }
+}
+
+fn to_image(samples: &Samples, color_space: &ColorSpace, decode: &[(f32, f32)]) -> DynamicImage {
+    let count = components(color_space);
+    let max = samples.max();
+    let gray = is_gray(color_space);
+
+    // Single component images are converted through a lookup table, which avoids evaluating
+    // tint transforms and lookups for each pixel
+    let lut = (count == 1 && samples.bits_per_component <= 8).then(|| {
+        (0..=samples.max() as u16)
+            .map(|value| {
+                let (min, dmax) = decode[0];
+                let value = min + f32::from(value) * (dmax - min) / max;
+                to_rgb(color_space, &[value])
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut values = vec![0.0; count];
+    let mut rgb = |pixel: &[u16]| {
+        if let Some(lut) = &lut {
+            return lut[usize::from(pixel[0]).min(lut.len() - 1)];
+        }
+
+        for ((value, &sample), &(min, dmax)) in values.iter_mut().zip(pixel).zip(decode) {
+            *value = min + f32::from(sample) * (dmax - min) / max;
+        }
+        to_rgb(color_space, &values)
+    };
+
+    let pixels = samples.values.chunks_exact(count);
+    let to_u8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    if gray {
+        let data = pixels.map(|pixel| to_u8(rgb(pixel)[0])).collect();
+        GrayImage::from_raw(samples.width, samples.height, data).map_or_else(
+            || DynamicImage::new_luma8(samples.width, samples.height),
+            DynamicImage::ImageLuma8,
+        )
+    } else {
+        let data = pixels
+            .flat_map(|pixel| rgb(pixel).map(to_u8))
+            .collect::<Vec<_>>();
+        RgbImage::from_raw(samples.width, samples.height, data).map_or_else(
+            || DynamicImage::new_rgb8(samples.width, samples.height),
+            DynamicImage::ImageRgb8,
+        )
+    }
+}
+
+/// Converts decoded component values to rgb, approximating device dependent colour spaces.
+fn to_rgb(color_space: &ColorSpace, values: &[f32]) -> [f32; 3] {
+    let get = |i: usize| values.get(i).copied().unwrap_or_default();
+
+    match color_space {
+        ColorSpace::DeviceGray | ColorSpace::CalGray(_) | ColorSpace::Pattern => {
+            [get(0), get(0), get(0)]
+        }
+        ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) => [get(0), get(1), get(2)],
+        ColorSpace::DeviceCMYK | ColorSpace::CalCMYK(_) => {
+            let k = 1.0 - get(3);
+            [(1.0 - get(0)) * k, (1.0 - get(1)) * k, (1.0 - get(2)) * k]
+        }
+        ColorSpace::Icc(icc) => match &icc.info.info.alternate {
+            Some(alternate) => to_rgb(alternate, values),
+            None => match icc.info.info.components {
+                1 => to_rgb(&ColorSpace::DeviceGray, values),
+                4 => to_rgb(&ColorSpace::DeviceCMYK, values),
+                _ => to_rgb(&ColorSpace::DeviceRGB, values),
+            },
+        },
+        ColorSpace::Indexed(base, hival, lookup) => {
+            let n = components(base);
+            let index = (get(0).round().max(0.0) as usize).min(usize::from(*hival));
+            let base_values = (0..n)
+                .map(|i| {
+                    lookup
+                        .get(index * n + i)
+                        .map_or(0.0, |&value| f32::from(value) / 255.0)
+                })
+                .collect::<Vec<_>>();
+            let decode = decode_ranges(
+                base,
+                None,
+                &Samples {
+                    width: 0,
+                    height: 0,
+                    bits_per_component: 8,
+                    values: Vec::new(),
+                },
+            );
+            let base_values = base_values
+                .iter()
+                .zip(decode)
+                .map(|(value, (min, max))| min + value * (max - min))
+                .collect::<Vec<_>>();
+            to_rgb(base, &base_values)
+        }
+        ColorSpace::Separation(_, alternate, tint)
+        | ColorSpace::DeviceN {
+            alt: alternate,
+            tint,
+            ..
+        } => {
+            let mut alternate_values = vec![0.0; components(alternate)];
+            match tint.apply(values, &mut alternate_values) {
+                Ok(()) => to_rgb(alternate, &alternate_values),
+                // Colourants are subtractive, full tint is black
+                Err(_) => {
+                    let value = 1.0 - values.iter().copied().fold(0.0, f32::max);
+                    [value, value, value]
+                }
+            }
+        }
+        ColorSpace::Other(array) if is_lab(array) => lab_to_rgb(array, get(0), get(1), get(2)),
+        ColorSpace::Named(_) | ColorSpace::Other(_) => [get(0), get(0), get(0)],
+    }
+}
+
+/// CIE L*a*b* to sRGB, relative to the colour space white point.
+fn lab_to_rgb(array: &[Primitive], l: f32, a: f32, b: f32) -> [f32; 3] {
+    let white = lab_dict(array)
+        .and_then(|dict| dict.get("WhitePoint").cloned())
+        .and_then(|point| point.into_array().ok())
+        .map(|point| {
+            point
+                .iter()
+                .filter_map(|value| value.as_number().ok())
+                .collect::<Vec<_>>()
+        })
+        .filter(|point| point.len() == 3)
+        .unwrap_or_else(|| vec![0.9505, 1.0, 1.089]);
+
+    let f = |t: f32| {
+        if t > 6.0 / 29.0 {
+            t.powi(3)
+        } else {
+            3.0 * (6.0_f32 / 29.0).powi(2) * (t - 4.0 / 29.0)
+        }
+    };
+    let m = (l + 16.0) / 116.0;
+    let x = white[0] * f(m + a / 500.0);
+    let y = white[1] * f(m);
+    let z = white[2] * f(m - b / 200.0);
+
+    let gamma = |c: f32| {
+        if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    };
+
+    [
+        gamma(3.2406 * x - 1.5372 * y - 0.4986 * z),
+        gamma(-0.9689 * x + 1.8758 * y + 0.0415 * z),
+        gamma(0.0557 * x - 0.2040 * y + 1.0570 * z),
+    ]
+}
+
+/// An image mask paints (in black) the samples equal to 0, or to 1 with an inverted decode array.
+fn stencil(samples: &Samples, decode: Option<&[f32]>) -> DynamicImage {
+    let paint = u16::from(is_inverted(decode));
+    let data = samples
+        .values
+        .iter()
+        .flat_map(|&value| [0, if value == paint { 255 } else { 0 }])
+        .collect();
+
+    GrayAlphaImage::from_raw(samples.width, samples.height, data).map_or_else(
+        || DynamicImage::new_luma_a8(samples.width, samples.height),
+        DynamicImage::ImageLumaA8,
+    )
+}
+
+/// Colour key masking, samples whose components are all in the given ranges are transparent.
+fn color_key_mask(samples: &Samples, ranges: &[Primitive], resolver: &impl Resolve) -> GrayImage {
+    let ranges = ranges
+        .iter()
+        .filter_map(|value| value.clone().resolve(resolver).ok()?.as_integer().ok())
+        .collect::<Vec<_>>();
+    let count = ranges.len() / 2;
+
+    let data = samples
+        .values
+        .chunks_exact(count.max(1))
+        .map(|pixel| {
+            let masked = count > 0
+                && pixel
+                    .iter()
+                    .zip(ranges.chunks_exact(2))
+                    .all(|(&value, range)| (range[0]..=range[1]).contains(&i32::from(value)));
+            if masked { 0 } else { 255 }
+        })
+        .collect();
+
+    GrayImage::from_raw(samples.width, samples.height, data)
+        .unwrap_or_else(|| GrayImage::from_pixel(samples.width, samples.height, [255].into()))
+}
+
+/// A soft mask is a gray image whose values are the opacity of the masked image.
+fn soft_mask(resolver: &impl Resolve, smask: &ImageXObject) -> Result<GrayImage, PdfError> {
+    let img = decode_img(resolver, smask)?;
+
+    Ok(img.to_luma8())
+}
+
+/// An explicit mask is a stencil whose samples equal to 1 (or 0 when inverted) are masked out.
+fn explicit_mask(resolver: &impl Resolve, mask: &ImageXObject) -> Result<GrayImage, PdfError> {
+    let Decoded::Samples(samples) = decode_data(resolver, mask)? else {
+        return Err(PdfError::UnsupportedImage("explicit mask must be bi-level"));
+    };
+    let masked = u16::from(!is_inverted(mask.decode.as_deref()));
+    let data = samples
+        .values
+        .iter()
+        .map(|&value| if value == masked { 0 } else { 255 })
+        .collect();
+
+    Ok(GrayImage::from_raw(samples.width, samples.height, data)
+        .unwrap_or_else(|| GrayImage::from_pixel(samples.width, samples.height, [255].into())))
+}
+
+fn with_alpha(img: DynamicImage, alpha: &GrayImage) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    let alpha = if alpha.dimensions() == (width, height) {
+        alpha.clone()
+    } else {
+        image::imageops::resize(alpha, width, height, FilterType::Triangle)
+    };
+
+    if img.color().has_color() {
+        let mut rgba: RgbaImage = img.into_rgba8();
+        for (pixel, opacity) in rgba.pixels_mut().zip(alpha.pixels()) {
+            pixel[3] = opacity[0];
+        }
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        let mut luma_alpha = img.into_luma_alpha8();
+        for (pixel, opacity) in luma_alpha.pixels_mut().zip(alpha.pixels()) {
+            pixel[1] = opacity[0];
+        }
+        DynamicImage::ImageLumaA8(luma_alpha)
+    }
+}
+
+/// Composes an image with an alpha channel onto a white background.
+pub(super) fn flatten(img: DynamicImage) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return img;
+    }
+
+    let blend = |value: u8, alpha: u8| {
+        let (value, alpha) = (u16::from(value), u16::from(alpha));
+        ((value * alpha + 255 * (255 - alpha)) / 255) as u8
+    };
+
+    if img.color().has_color() {
+        let rgba = img.into_rgba8();
+        let rgb = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let [r, g, b, a] = rgba.get_pixel(x, y).0;
+            [blend(r, a), blend(g, a), blend(b, a)].into()
+        });
+        DynamicImage::ImageRgb8(rgb)
+    } else {
+        let luma_alpha = img.into_luma_alpha8();
+        let luma = GrayImage::from_fn(luma_alpha.width(), luma_alpha.height(), |x, y| {
+            let [l, a] = luma_alpha.get_pixel(x, y).0;
+            [blend(l, a)].into()
+        });
+        DynamicImage::ImageLuma8(luma)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8x2 image, a black row then a white one, encoded with CCITT group 4 (MMR).
+    const MMR_DATA: &[u8] = &[0x26, 0xa2, 0x99, 0x86, 0xe0];
+
+    fn ccitt_params(columns: u32, rows: u32) -> CCITTFaxDecodeParams {
+        CCITTFaxDecodeParams {
+            k: -1,
+            end_of_line: false,
+            encoded_byte_align: false,
+            columns,
+            rows,
+            end_of_block: true,
+            black_is_1: false,
+            damaged_rows_before_error: 0,
+        }
+    }
+
+    fn expected_rows() -> Vec<u16> {
+        [[0; 8], [1; 8]].concat()
+    }
+
+    #[test]
+    fn test_unpack() -> Result<(), PdfError> {
+        let samples = Samples::unpack(&[0b1011_0000, 0b0100_0000], 3, 2, 1, 1)?;
+        assert_eq!(samples.values, [1, 0, 1, 0, 1, 0]);
+
+        let samples = Samples::unpack(&[0b1110_0100, 0x12], 3, 1, 1, 4)?;
+        assert_eq!(samples.values, [0xE, 0x4, 0x1]);
+        assert_eq!(samples.max(), 15.0);
+
+        let samples = Samples::unpack(&[0x12, 0x34], 2, 1, 1, 16)?;
+        assert_eq!(samples.values, [0x1234, 0]);
+
+        assert!(Samples::unpack(&[0xFF], 2, 1, 1, 3).is_err());
+        assert!(Samples::unpack(&[0xFF], 0, 1, 1, 8).is_err());
+        assert!(Samples::unpack(&[], u32::MAX, u32::MAX, 4, 8).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ccitt_decode() -> Result<(), PdfError> {
+        let samples = ccitt_decode(MMR_DATA, &ccitt_params(8, 2), 8, 2)?;
+        assert_eq!(samples.values, expected_rows());
+
+        // Missing rows are white, and columns are cropped (or padded) to the image width
+        let samples = ccitt_decode(MMR_DATA, &ccitt_params(8, 0), 4, 3)?;
+        assert_eq!(samples.values, [[0; 4], [1; 4], [1; 4]].concat());
+
+        assert!(ccitt_decode(MMR_DATA, &ccitt_params(0, 2), 8, 2).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jbig2_decode() -> Result<(), PdfError> {
+        let page_info = [
+            &[0, 0, 0, 0, 48, 0, 1, 0, 0, 0, 19][..],
+            &[0, 0, 0, 8, 0, 0, 0, 2],
+            &[0; 11],
+        ];
+        let region = [
+            &[0, 0, 0, 1, 38, 0, 1, 0, 0, 0, 23][..],
+            &[0, 0, 0, 8, 0, 0, 0, 2],
+            &[0; 9],
+            // MMR coded
+            &[1],
+            MMR_DATA,
+        ];
+        let data = [page_info.concat(), region.concat()].concat();
+
+        let samples = jbig2_decode(&data, None)?;
+        assert_eq!((samples.width, samples.height), (8, 2));
+        assert_eq!(samples.values, expected_rows());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
 use std::io;
 
-use reco::{ArchiveFinishError, CreateArchiveError, InsertionError};
+use image::ImageError;
+use reco::{ArchiveFinishError, CreateArchiveError, InsertionError, PipelineCreationError};
 
-use crate::converters::PdfError;
+use crate::converters::{ArchiveError, EpubError, MobiError, PdfError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -15,9 +16,24 @@ pub enum Error {
     #[error("finishing archive: {0}")]
     FinishArchive(#[from] ArchiveFinishError),
 
+    #[error("creating pipeline: {0}")]
+    CreatePipeline(#[from] PipelineCreationError),
+
     #[error(transparent)]
     Pdf(#[from] PdfError),
 
+    #[error(transparent)]
+    Epub(#[from] EpubError),
+
+    #[error(transparent)]
+    Mobi(#[from] MobiError),
+
+    #[error(transparent)]
+    Archive(#[from] ArchiveError),
+
+    #[error("decoding image: {0}")]
+    ImageDecode(#[from] ImageError),
+
     #[error("opening file: {0}")]
     FileOpen(io::Error),
 
@@ -1,43 +1,88 @@
 use std::path::Path;
 
-use converters::Pdf;
+use converters::{Archive, Epub, Mobi, Pdf};
 pub use errors::Error;
-use image::DynamicImage;
-use reco::Writer as CbzWriter;
-use tracing::error;
-pub use types::Format;
+use reco::{NoProgress, Pipeline, Progress, Report, Writer as CbzWriter};
+use types::Page;
+pub use types::{Format, Options};
 
 mod converters;
 mod errors;
 mod types;
 
-pub fn convert(input_path: &Path, output_path: &Path) -> Result<(), Error> {
+pub fn convert(input_path: &Path, output_path: &Path, opts: Options) -> Result<Report, Error> {
+    convert_with_progress(input_path, output_path, opts, &NoProgress)
+}
+
+/// Converts the file, reporting the pages written to `progress` (the total is the page count
+/// guessed from the source file).
+pub fn convert_with_progress(
+    input_path: &Path,
+    output_path: &Path,
+    opts: Options,
+    progress: &dyn Progress,
+) -> Result<Report, Error> {
     let format = Format::try_from_path(input_path)?;
 
-    let imgs: Box<dyn Iterator<Item = Result<DynamicImage, Error>>> = match format {
+    let pages: Box<dyn Iterator<Item = Result<Page, Error>>> = match format {
         // TODO: Add other formats
         Format::Pdf => {
             let pdf = Pdf::try_from_path(input_path)?;
             Box::new(pdf.into_iter())
         }
+        Format::Epub => {
+            let epub = Epub::try_from_path(input_path)?;
+            Box::new(epub.into_iter())
+        }
+        Format::Mobi => {
+            let mobi = Mobi::try_from_path(input_path)?;
+            Box::new(mobi.into_iter())
+        }
+        Format::Archive => {
+            let archive = Archive::try_from_path(input_path, opts.reader)?;
+            Box::new(archive.into_iter())
+        }
     };
 
-    let mut cbz = CbzWriter::create_from_path(output_path)?;
-    for res in imgs {
-        let img = match res {
-            Ok(img) => img,
-            Err(err) => {
-                error!("image error: {err}");
-                continue;
+    progress.start_file(input_path);
+    progress.add_total(pages.size_hint().0);
+
+    let pipeline = Pipeline::new(opts.jobs)?;
+    let mut cbz = CbzWriter::create_from_path_with_options(output_path, opts.writer)?;
+    let encoder = cbz.encoder();
+    let mut report = Report::default();
+
+    pipeline.run(
+        pages.enumerate(),
+        |(index, res)| {
+            let res = res.and_then(|page| page.encode(&encoder));
+            (index, res)
+        },
+        |(index, res)| {
+            let res = res.and_then(|page| {
+                cbz.insert_encoded(&page)?;
+                Ok(page.len())
+            });
+            match res {
+                Ok(len) => {
+                    report.pages += 1;
+                    progress.page_done(len as u64);
+                }
+                Err(err) => {
+                    let input = format!("page {}", index + 1);
+                    if report.record(opts.error_policy, input, err, true)? {
+                        cbz.insert_placeholder()?;
+                        report.pages += 1;
+                    }
+                    progress.page_done(0);
+                }
             }
-        };
 
-        if let Err(err) = cbz.insert_image_as_jpeg(&img, 75) {
-            error!("image insertion error: {err}");
-        }
-    }
+            Ok::<_, Error>(())
+        },
+    )?;
 
     cbz.finish()?;
 
-    Ok(())
+    Ok(report)
 }
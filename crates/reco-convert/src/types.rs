@@ -1,16 +1,123 @@
-use std::{fs::OpenOptions, io::Read, path::Path};
+use std::{
+    fs::OpenOptions,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use image::{DynamicImage, ImageError, ImageReader};
+use reco::{
+    ErrorPolicy,
+    reader::Options as ReaderOptions,
+    writer::{EncodedPage, Encoder, Options as WriterOptions},
+};
 
 use crate::Error;
 
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub(crate) reader: ReaderOptions,
+    pub(crate) writer: WriterOptions,
+    pub(crate) jobs: usize,
+    pub(crate) error_policy: ErrorPolicy,
+}
+
+impl Options {
+    /// How comic archives (cbz, cbr, cb7...) are read, e.g. the encoding of their entry names.
+    #[must_use]
+    pub fn with_reader_options(mut self, reader: ReaderOptions) -> Self {
+        self.reader = reader;
+        self
+    }
+
+    #[must_use]
+    pub fn with_writer_options(mut self, writer: WriterOptions) -> Self {
+        self.writer = writer;
+        self
+    }
+
+    /// The number of threads pages are decoded (or rendered) and encoded on, one per core if 0
+    /// (the default). Pages are still read from the source file one after another.
+    #[must_use]
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// What to do with the pages which can't be extracted or inserted (skipped by default).
+    #[must_use]
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+}
+
+/// A page produced by a converter.
+pub enum Page {
+    /// A decoded image, encoded by the writer.
+    Image(DynamicImage),
+    /// An image already encoded in the source file, written as is.
+    Encoded { ext: String, buf: Vec<u8> },
+    /// A page whose extraction (e.g. decoding or rendering) is deferred to the pipeline threads.
+    Lazy(Box<dyn FnOnce() -> Result<Page, Error> + Send>),
+}
+
+/// The image formats of the source files written as is, without being decoded.
+static PASSTHROUGH_FORMATS: &[&str] = &["jpg", "png", "gif", "webp"];
+
+impl Page {
+    /// An image of the source file, written as is if comic readers support its format, decoded
+    /// (on the pipeline threads) otherwise.
+    pub(crate) fn from_buf(buf: Vec<u8>) -> Self {
+        if let Some(ext) = infer::get(&buf)
+            .map(|kind| kind.extension())
+            .filter(|ext| PASSTHROUGH_FORMATS.contains(ext))
+        {
+            return Self::Encoded {
+                ext: format!(".{ext}"),
+                buf,
+            };
+        }
+
+        Self::Lazy(Box::new(move || {
+            let img = ImageReader::new(Cursor::new(buf))
+                .with_guessed_format()
+                .map_err(ImageError::IoError)?
+                .decode()?;
+
+            Ok(Self::Image(img))
+        }))
+    }
+
+    /// Encodes the page, extracting it first if it's lazy.
+    pub(crate) fn encode(self, encoder: &Encoder) -> Result<EncodedPage, Error> {
+        match self {
+            Self::Image(img) => Ok(encoder.encode_image(&img)?),
+            Self::Encoded { ext, buf } => Ok(encoder.encode_buf(&ext, buf)?),
+            Self::Lazy(extract) => extract()?.encode(encoder),
+        }
+    }
+}
+
+/// Enough to hold the tar magic, at offset 257.
+const HEADER_LEN: usize = 262;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
-    // TODO: Add Mobi, Azw3, etc...
     Pdf,
+    Epub,
+    /// Mobipocket based formats, MOBI (KF7), AZW3 (KF8) and combined KF7/KF8 files.
+    Mobi,
+    /// Comic archives, CBZ (zip), CBR (rar), CB7 (7z) and CBT (tar), or directories of images.
+    Archive,
 }
 
 impl Format {
     pub fn try_from_path(path: &Path) -> Result<Self, Error> {
-        let mut file = OpenOptions::new()
+        if path.is_dir() {
+            return Ok(Self::Archive);
+        }
+
+        let file = OpenOptions::new()
             .read(true)
             .write(false)
             .create(false)
@@ -18,13 +125,31 @@ impl Format {
             .open(path)
             .map_err(Error::FileOpen)?;
 
-        let mut buf = [0; 32];
-        file.read_exact(&mut buf).map_err(Error::FileRead)?;
+        // Some signatures (e.g. epub) are located after the zip local file header
+        let mut buf = Vec::with_capacity(HEADER_LEN);
+        file.take(HEADER_LEN as u64)
+            .read_to_end(&mut buf)
+            .map_err(Error::FileRead)?;
 
         if infer::is(&buf, "pdf") {
             return Ok(Self::Pdf);
         }
 
+        if infer::is(&buf, "epub") {
+            return Ok(Self::Epub);
+        }
+
+        if infer::is(&buf, "mobi") {
+            return Ok(Self::Mobi);
+        }
+
+        if ["zip", "rar", "7z", "tar", "gz"]
+            .into_iter()
+            .any(|ext| infer::is(&buf, ext))
+        {
+            return Ok(Self::Archive);
+        }
+
         Err(Error::UnknownFormat)
     }
 }
@@ -0,0 +1,508 @@
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, ImageError, ImageFormat};
+use lexical_sort::{StringSort, natural_lexical_cmp};
+use tracing::debug;
+
+use reco::{
+    ArchiveFinishError, BackendError, ComicInfo, CreateArchiveError,
+    InsertionError as CbzInsertionError, LoadComicInfoError, Naming, PageSelection,
+    Reader as CbzReader, ReaderOpenError as CbzReaderOpenError, Writer as CbzWriter,
+    comic_info::{COMIC_INFO_PATH, PageInfo},
+    encoding::{EncodingOptions, ImageFormat as EncodingImageFormat},
+    reader::Options as ReaderOptions,
+    writer::{Format as WriterFormat, Options as WriterOptions, split_entry_name},
+};
+
+/// The JPEG quality transformed JPEG pages are encoded with, unless an encoding is given.
+static JPEG_QUALITY: u8 = 90;
+
+/// Clockwise rotations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flip {
+    Horizontal,
+    Vertical,
+}
+
+/// An edit of the pages, numbered from 1 in the order they have after the previous operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Delete(PageSelection),
+    /// Moves the pages, in order, before the page `before` (after the last page if it is the page
+    /// count + 1).
+    Move {
+        pages: PageSelection,
+        before: usize,
+    },
+    /// Inserts the images, in order, before the page `before` (after the last page if `None`).
+    Insert {
+        paths: Vec<PathBuf>,
+        before: Option<usize>,
+    },
+    Rotate(PageSelection, Rotation),
+    Flip(PageSelection, Flip),
+    Swap(usize, usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    operations: Vec<Operation>,
+    reader: ReaderOptions,
+    encoding: Option<EncodingOptions>,
+}
+
+impl Options {
+    /// Operations are applied in order.
+    #[must_use]
+    pub fn with_operation(mut self, operation: Operation) -> Self {
+        self.operations.push(operation);
+        self
+    }
+
+    /// How the pages are ordered, and so numbered.
+    #[must_use]
+    pub fn with_reader_options(mut self, reader: ReaderOptions) -> Self {
+        self.reader = reader;
+        self
+    }
+
+    /// How rotated or flipped pages are encoded, in their original format otherwise.
+    #[must_use]
+    pub fn with_encoding(mut self, encoding: EncodingOptions) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("cbz open error: {0}")]
+    CbzOpen(#[from] CbzReaderOpenError),
+
+    #[error("comic info load error: {0}")]
+    ComicInfoLoad(#[from] LoadComicInfoError),
+
+    #[error("archive entry read error: {0}")]
+    EntryRead(#[from] BackendError),
+
+    #[error("{0} read error: {1}")]
+    ImageRead(PathBuf, io::Error),
+
+    #[error("image decoding error: {0}")]
+    ImageDecode(ImageError),
+
+    #[error("image encoding error: {0}")]
+    ImageEncode(ImageError),
+
+    #[error("archive creation error: {0}")]
+    ArchiveCreation(#[from] CreateArchiveError),
+
+    #[error("cbz insertion error: {0}")]
+    CbzInsertion(#[from] CbzInsertionError),
+
+    #[error("archive creation error: {0}")]
+    ArchiveFinish(#[from] ArchiveFinishError),
+
+    #[error("page {page} out of range, the archive has {count} pages")]
+    PageOutOfRange { page: usize, count: usize },
+
+    #[error("no page selected, the archive has {0} pages")]
+    NoPageSelected(usize),
+
+    #[error("{0} is not a cbz, only cbz archives can be written")]
+    UnsupportedOutput(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Transform {
+    Rotate(Rotation),
+    Flip(Flip),
+}
+
+impl Transform {
+    fn apply(self, img: &DynamicImage) -> DynamicImage {
+        match self {
+            Self::Rotate(Rotation::Rotate90) => img.rotate90(),
+            Self::Rotate(Rotation::Rotate180) => img.rotate180(),
+            Self::Rotate(Rotation::Rotate270) => img.rotate270(),
+            Self::Flip(Flip::Horizontal) => img.fliph(),
+            Self::Flip(Flip::Vertical) => img.flipv(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Entry(String),
+    File(PathBuf),
+}
+
+/// A page of the edited archive.
+#[derive(Debug, Clone)]
+struct Page {
+    source: Source,
+    /// The page spine index in the source archive, if it comes from it.
+    index: Option<usize>,
+    transforms: Vec<Transform>,
+}
+
+impl Page {
+    /// The name the page keeps, its entry name or the inserted file name.
+    fn name(&self) -> String {
+        match &self.source {
+            Source::Entry(name) => name.clone(),
+            Source::File(path) => path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+}
+
+/// Whether the pages, named after their source, are still in the natural order of their names.
+fn keeps_order(pages: &[Page]) -> bool {
+    pages
+        .windows(2)
+        .all(|pages| natural_lexical_cmp(&pages[0].name(), &pages[1].name()) == Ordering::Less)
+}
+
+/// Applies the operations to the archive pages and writes the result as a cbz to `output`, which
+/// can be the archive itself. Untouched pages and the other entries are copied as is (without
+/// being decompressed from a zip), rotated or flipped ones are re-encoded. The pages keep their
+/// names (and folders) unless they no longer sort in their edited order, they are then renamed
+/// after their index. The `ComicInfo.xml` pages and the zip comment are kept.
+pub fn edit(path: &Path, output: &Path, opts: &Options) -> Result<(), Error> {
+    let is_cbz = output
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cbz") || ext.eq_ignore_ascii_case("zip"));
+    if !is_cbz {
+        return Err(Error::UnsupportedOutput(output.to_path_buf()));
+    }
+
+    let mut cbz_reader = CbzReader::try_open_with_options(path, opts.reader)?;
+    let file_names = cbz_reader
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    let mut pages = file_names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| Page {
+            source: Source::Entry(name.clone()),
+            index: Some(index),
+            transforms: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    for operation in &opts.operations {
+        apply(operation, &mut pages)?;
+    }
+
+    let comic_info = cbz_reader
+        .comic_info()?
+        .map(|comic_info| edited_comic_info(&comic_info, &file_names, &pages));
+    let comment = cbz_reader.comment().to_vec();
+
+    // The writer only replaces the output once finished, after the archive is closed
+    write(cbz_reader, &pages, comic_info, &comment, output, opts)
+}
+
+fn apply(operation: &Operation, pages: &mut Vec<Page>) -> Result<(), Error> {
+    let count = pages.len();
+    let selected = |selection: &PageSelection| {
+        let selected = selection.pages(count);
+        if selected.is_empty() {
+            return Err(Error::NoPageSelected(count));
+        }
+        Ok(selected)
+    };
+    let check = |page: usize, max: usize| {
+        if page == 0 || page > max {
+            return Err(Error::PageOutOfRange { page, count });
+        }
+        Ok(page)
+    };
+
+    match operation {
+        Operation::Delete(selection) => {
+            let selected = selected(selection)?;
+            let mut page = 0;
+            pages.retain(|_| {
+                page += 1;
+                selected.binary_search(&page).is_err()
+            });
+        }
+        Operation::Move {
+            pages: selection,
+            before,
+        } => {
+            let selected = selected(selection)?;
+            let before = check(*before, count + 1)?;
+
+            let (mut moved, mut kept) = (Vec::new(), Vec::new());
+            for (page, item) in (1..).zip(pages.drain(..)) {
+                if selected.binary_search(&page).is_ok() {
+                    moved.push(item);
+                } else {
+                    kept.push(item);
+                }
+            }
+
+            let at = (1..before)
+                .filter(|page| selected.binary_search(page).is_err())
+                .count();
+            kept.splice(at..at, moved);
+            *pages = kept;
+        }
+        Operation::Insert { paths, before } => {
+            let at = match before {
+                Some(before) => check(*before, count + 1)? - 1,
+                None => count,
+            };
+            pages.splice(
+                at..at,
+                paths.iter().map(|path| Page {
+                    source: Source::File(path.clone()),
+                    index: None,
+                    transforms: Vec::new(),
+                }),
+            );
+        }
+        Operation::Rotate(selection, rotation) => {
+            for page in selected(selection)? {
+                pages[page - 1]
+                    .transforms
+                    .push(Transform::Rotate(*rotation));
+            }
+        }
+        Operation::Flip(selection, flip) => {
+            for page in selected(selection)? {
+                pages[page - 1].transforms.push(Transform::Flip(*flip));
+            }
+        }
+        Operation::Swap(a, b) => pages.swap(check(*a, count)? - 1, check(*b, count)? - 1),
+    }
+
+    Ok(())
+}
+
+/// The `ComicInfo` with its pages following the edited ones. Page indices refer to the naturally
+/// sorted entries, which for the written archive is the order of the pages.
+fn edited_comic_info(comic_info: &ComicInfo, file_names: &[String], pages: &[Page]) -> ComicInfo {
+    let mut natural = file_names.to_vec();
+    natural.string_sort_unstable(natural_lexical_cmp);
+
+    let mut edited = comic_info.clone();
+    edited.pages = comic_info
+        .pages
+        .iter()
+        .filter_map(|info| {
+            let name = natural.get(info.image)?;
+            let index = file_names.iter().position(|other| other == name)?;
+            let image = pages.iter().position(|page| page.index == Some(index))?;
+
+            // The image of a transformed page changed
+            if pages[image].transforms.is_empty() {
+                Some(PageInfo {
+                    image,
+                    ..info.clone()
+                })
+            } else {
+                Some(PageInfo {
+                    image,
+                    image_size: None,
+                    image_width: None,
+                    image_height: None,
+                    ..info.clone()
+                })
+            }
+        })
+        .collect();
+    edited.pages.sort_by_key(|info| info.image);
+
+    edited
+}
+
+fn write(
+    mut cbz_reader: CbzReader,
+    pages: &[Page],
+    comic_info: Option<ComicInfo>,
+    comment: &[u8],
+    path: &Path,
+    opts: &Options,
+) -> Result<(), Error> {
+    let naming = if keeps_order(pages) {
+        Naming::Original
+    } else {
+        debug!("the edited pages don't sort by name, naming them after their index");
+        Naming::default()
+    };
+    let mut writer = CbzWriter::create_from_path_with_options(
+        path,
+        WriterOptions::new(WriterFormat::Cbz).with_naming(naming),
+    )?;
+    let read_error = |err| match err {
+        CbzInsertionError::EntryRead(err) => Error::EntryRead(err),
+        err => err.into(),
+    };
+
+    // The entries which aren't pages (nor the metadata, written when finished) are kept as is
+    let page_names = cbz_reader
+        .iter()
+        .map(ToString::to_string)
+        .collect::<HashSet<_>>();
+    let others = cbz_reader
+        .file_names()
+        .iter()
+        .filter(|name| {
+            !page_names.contains(name.as_str()) && !name.eq_ignore_ascii_case(COMIC_INFO_PATH)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    for name in others {
+        writer
+            .copy_raw_sidecar(&mut cbz_reader, &name)
+            .map_err(read_error)?;
+    }
+
+    for page in pages {
+        if let (Source::Entry(name), true) = (&page.source, page.transforms.is_empty()) {
+            writer.copy_raw(&mut cbz_reader, name).map_err(read_error)?;
+            continue;
+        }
+
+        let buf = match &page.source {
+            Source::Entry(name) => cbz_reader.read(name)?,
+            Source::File(path) => {
+                fs::read(path).map_err(|err| Error::ImageRead(path.clone(), err))?
+            }
+        };
+        let name = page.name();
+        let (stem, ext) = split_entry_name(&name);
+
+        if page.transforms.is_empty() {
+            let mut encoded = writer.encoder().encode_buf(ext, buf)?;
+            if let Some(stem) = stem {
+                encoded = encoded.with_name(stem);
+            }
+            writer.insert_encoded(&encoded)?;
+            continue;
+        }
+
+        debug!("transforming {name}");
+        let format = image::guess_format(&buf).map_err(Error::ImageDecode)?;
+        let mut img =
+            image::load_from_memory_with_format(&buf, format).map_err(Error::ImageDecode)?;
+        for transform in &page.transforms {
+            img = transform.apply(&img);
+        }
+
+        let encoding = opts.encoding.unwrap_or_else(|| match format {
+            ImageFormat::Png => EncodingOptions::new(EncodingImageFormat::Png),
+            ImageFormat::WebP => EncodingOptions::new(EncodingImageFormat::WebP),
+            ImageFormat::Avif => EncodingOptions::new(EncodingImageFormat::Avif),
+            _ => EncodingOptions::new(EncodingImageFormat::Jpeg).with_quality(JPEG_QUALITY),
+        });
+        let buf = encoding.encode(&img).map_err(Error::ImageEncode)?;
+        let mut encoded = writer
+            .encoder()
+            .encode_buf(encoding.format().extension(), buf)?;
+        if let Some(stem) = stem {
+            encoded = encoded.with_name(stem);
+        }
+        writer.insert_encoded(&encoded)?;
+    }
+
+    writer.set_comic_info(comic_info);
+    writer.set_comment(comment);
+    // The output can be the archive itself, which can't be replaced while open on some platforms
+    drop(cbz_reader);
+    writer.finish()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(count: usize) -> Vec<Page> {
+        (0..count)
+            .map(|index| Page {
+                source: Source::Entry(index.to_string()),
+                index: Some(index),
+                transforms: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn indices(pages: &[Page]) -> Vec<Option<usize>> {
+        pages.iter().map(|page| page.index).collect()
+    }
+
+    #[test]
+    fn test_apply() -> Result<(), Box<dyn std::error::Error>> {
+        let mut edited = pages(6);
+        apply(
+            &Operation::Move {
+                pages: "2-3".parse()?,
+                before: 6,
+            },
+            &mut edited,
+        )?;
+        assert_eq!(indices(&edited), [0, 3, 4, 1, 2, 5].map(Some));
+
+        apply(&Operation::Delete("1,6".parse()?), &mut edited)?;
+        apply(&Operation::Swap(1, 4), &mut edited)?;
+        apply(
+            &Operation::Insert {
+                paths: vec![PathBuf::from("cover.jpg")],
+                before: Some(1),
+            },
+            &mut edited,
+        )?;
+        assert_eq!(indices(&edited), [None, Some(2), Some(4), Some(1), Some(3)]);
+
+        assert!(apply(&Operation::Swap(1, 6), &mut edited).is_err());
+        assert!(apply(&Operation::Delete("7-".parse()?), &mut edited).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keeps_order() -> Result<(), Box<dyn std::error::Error>> {
+        let mut edited = pages(12);
+        apply(&Operation::Delete("2-3".parse()?), &mut edited)?;
+        apply(
+            &Operation::Rotate("1".parse()?, Rotation::Rotate90),
+            &mut edited,
+        )?;
+        apply(
+            &Operation::Insert {
+                paths: vec![PathBuf::from("extras/x.jpg")],
+                before: None,
+            },
+            &mut edited,
+        )?;
+        assert!(keeps_order(&edited));
+
+        apply(&Operation::Swap(1, 2), &mut edited)?;
+        assert!(!keeps_order(&edited));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use image::ImageError;
+use tracing::warn;
+
+use reco::{
+    BackendError, PageSelection, Reader as CbzReader, ReaderOpenError as CbzReaderOpenError,
+    encoding::EncodingOptions, reader::Options as ReaderOptions,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    reader: ReaderOptions,
+    encoding: Option<EncodingOptions>,
+    pages: Option<PageSelection>,
+}
+
+impl Options {
+    /// How the pages are ordered, and so numbered.
+    #[must_use]
+    pub fn with_reader_options(mut self, reader: ReaderOptions) -> Self {
+        self.reader = reader;
+        self
+    }
+
+    /// Re-encodes the pages, which are written unchanged otherwise.
+    #[must_use]
+    pub fn with_encoding(mut self, encoding: EncodingOptions) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Only extracts the selected pages, all of them otherwise.
+    #[must_use]
+    pub fn with_pages(mut self, pages: PageSelection) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("cbz open error: {0}")]
+    CbzOpen(#[from] CbzReaderOpenError),
+
+    #[error("archive entry read error: {0}")]
+    EntryRead(#[from] BackendError),
+
+    #[error("image decoding error: {0}")]
+    ImageDecode(ImageError),
+
+    #[error("image encoding error: {0}")]
+    ImageEncode(ImageError),
+
+    #[error("page write error: {0}")]
+    PageWrite(#[from] io::Error),
+}
+
+/// Extracts the pages of the archive into the directory (created if needed), named after their
+/// number in the spine, zero-padded to sort like it, e.g. `001.jpg`. Returns the written paths.
+pub fn extract(path: &Path, output: &Path, opts: &Options) -> Result<Vec<PathBuf>, Error> {
+    let mut cbz_reader = CbzReader::try_open_with_options(path, opts.reader)?;
+    let file_names = cbz_reader
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let width = file_names.len().to_string().len().max(3);
+
+    if let Some(pages) = &opts.pages
+        && !(1..=file_names.len()).any(|page| pages.contains(page))
+    {
+        warn!(
+            "no page selected, the archive has {} pages",
+            file_names.len()
+        );
+    }
+
+    fs::create_dir_all(output)?;
+    let mut written = Vec::new();
+
+    for (page, file_name) in (1..).zip(file_names) {
+        if opts
+            .pages
+            .as_ref()
+            .is_some_and(|pages| !pages.contains(page))
+        {
+            continue;
+        }
+
+        let mut buf = cbz_reader.read(&file_name)?;
+        let ext = match opts.encoding {
+            Some(encoding) => {
+                let img = image::load_from_memory(&buf).map_err(Error::ImageDecode)?;
+                buf = encoding.encode(&img).map_err(Error::ImageEncode)?;
+                encoding.format().extension().to_string()
+            }
+            None => Path::new(&file_name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or_default()
+                .to_ascii_lowercase(),
+        };
+
+        let mut page_path = output.join(format!("{page:0width$}"));
+        if !ext.is_empty() {
+            page_path.set_extension(ext);
+        }
+
+        fs::write(&page_path, buf)?;
+        written.push(page_path);
+    }
+
+    Ok(written)
+}
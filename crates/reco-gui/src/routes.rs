@@ -1,15 +1,13 @@
 #![expect(clippy::needless_pass_by_value)]
 
-use std::{
-    ffi::OsStr,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
 use egui::Ui;
 use egui_router::{
     EguiRouter, HandlerError, HandlerResult, Request, Route, TransitionConfig,
     history::DefaultHistory,
 };
+use reco::backend::has_archive_extension;
 
 use crate::{
     types::State,
@@ -21,11 +19,10 @@ type Handler = HandlerResult<Box<dyn FnMut(&mut egui::Ui, &mut State)>>;
 fn render(req: Request<State>) -> Handler {
     let path = req.params.get("path").ok_or(HandlerError::NotFound)?;
     let path = PathBuf::from(path);
-    let ext = path.extension().and_then(OsStr::to_str);
 
     let mut view: Box<dyn Route<State>> = if path.is_dir() {
         Box::new(DirectoryView::new(path))
-    } else if path.is_file() && ext == Some("cbz") {
+    } else if path.is_file() && has_archive_extension(&path) {
         Box::new(CbzView::new(path))
     } else {
         Box::new(UnknownFileView::new(path))
@@ -1,6 +1,7 @@
 use std::{fs::read_dir, io, path::PathBuf};
 
 use egui_router::Route;
+use reco::backend::has_archive_extension;
 use tracing::error;
 
 use super::back_button::back_button;
@@ -35,9 +36,7 @@ impl Route<State> for DirectoryView {
 
                 let path = entry.path();
                 let path_str = path.to_string_lossy();
-                let ext = path.extension().and_then(|ext| ext.to_str());
-
-                if path.is_dir() || (path.is_file() && ext == Some("cbz")) {
+                if path.is_dir() || (path.is_file() && has_archive_extension(&path)) {
                     paths.push(path_str.into_owned());
                 }
             }
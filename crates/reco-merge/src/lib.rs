@@ -1,14 +1,52 @@
 use std::path::Path;
 
 use glob::{GlobError, PatternError};
-use lexical_sort::{StringSort, natural_lexical_cmp};
-use tracing::error;
 
 use reco::{
-    ArchiveFinishError, CreateArchiveError, InsertionError as CbzInsertionError,
-    Reader as CbzReader, ReaderOpenError as CbzReaderOpenError, Writer as CbzWriter,
+    ArchiveFinishError, BackendError, CreateArchiveError, ErrorPolicy,
+    InsertionError as CbzInsertionError, NoProgress, Progress, Reader as CbzReader,
+    ReaderOpenError as CbzReaderOpenError, Report, Writer as CbzWriter,
+    reader::Options as ReaderOptions, writer::Options as WriterOptions,
 };
 
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    reader: ReaderOptions,
+    writer: WriterOptions,
+    append: bool,
+    error_policy: ErrorPolicy,
+}
+
+impl Options {
+    /// How the pages of each merged archive are ordered.
+    #[must_use]
+    pub fn with_reader_options(mut self, reader: ReaderOptions) -> Self {
+        self.reader = reader;
+        self
+    }
+
+    #[must_use]
+    pub fn with_writer_options(mut self, writer: WriterOptions) -> Self {
+        self.writer = writer;
+        self
+    }
+
+    /// Appends the pages to the existing cbz at the merge path, instead of creating it.
+    #[must_use]
+    pub fn with_append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// What to do with the archives which can't be opened and the pages which can't be read or
+    /// inserted (skipped by default).
+    #[must_use]
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("archive creation error: {0}")]
@@ -29,41 +67,72 @@ pub enum Error {
     #[error("cbz insertion error: {0}")]
     CbzInsertion(#[from] CbzInsertionError),
 
-    #[error("zip file error: {0}")]
-    ZipFile(#[from] zip::result::ZipError),
+    #[error("archive entry read error: {0}")]
+    EntryRead(#[from] BackendError),
 }
 
-pub fn merge(pattern: &str, path: &Path) -> Result<(), Error> {
+/// Merges the archives pages, copied without being decompressed when possible (see
+/// `Writer::copy_raw`).
+pub fn merge(pattern: &str, path: &Path, opts: Options) -> Result<Report, Error> {
+    merge_with_progress(pattern, path, opts, &NoProgress)
+}
+
+/// Merges the archives, reporting the pages copied to `progress`. The pages of an archive are
+/// added to the total once it's opened.
+pub fn merge_with_progress(
+    pattern: &str,
+    path: &Path,
+    opts: Options,
+    progress: &dyn Progress,
+) -> Result<Report, Error> {
     let paths = glob::glob(pattern)?;
-    let mut cbz = CbzWriter::create_from_path(path)?;
+    let mut cbz = if opts.append && path.exists() {
+        CbzWriter::append_from_path_with_options(path, opts.writer)?
+    } else {
+        CbzWriter::create_from_path_with_options(path, opts.writer)?
+    };
 
+    let mut report = Report::default();
     for path in paths {
         let path = path?;
-        let mut cbz_reader = CbzReader::try_open(&path)?;
-        let mut file_names = cbz_reader
+        let mut cbz_reader = match CbzReader::try_open_with_options(&path, opts.reader) {
+            Ok(cbz_reader) => cbz_reader,
+            Err(err) => {
+                report.record(opts.error_policy, path.display(), Error::from(err), false)?;
+                continue;
+            }
+        };
+        let file_names = cbz_reader
             .iter()
             .map(ToString::to_string)
             .collect::<Vec<_>>();
-
-        file_names.string_sort_unstable(natural_lexical_cmp);
+        progress.start_file(&path);
+        progress.add_total(file_names.len());
+        cbz.start_chapter();
 
         for file_name in file_names {
-            let file = cbz_reader.by_name(&file_name)?;
-            let path = Path::new(file.name());
-            let ext = path
-                .extension()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or_default()
-                .to_string();
-
-            if let Err(err) = cbz.insert_reader(&ext, file) {
-                error!("error inserting file: {err}");
+            match cbz.copy_raw(&mut cbz_reader, &file_name) {
+                Ok(len) => {
+                    report.pages += 1;
+                    progress.page_done(len);
+                }
+                Err(err) => {
+                    let err = match err {
+                        CbzInsertionError::EntryRead(err) => Error::EntryRead(err),
+                        err => Error::CbzInsertion(err),
+                    };
+                    let input = format!("{}:{file_name}", path.display());
+                    if report.record(opts.error_policy, input, err, true)? {
+                        cbz.insert_placeholder()?;
+                        report.pages += 1;
+                    }
+                    progress.page_done(0);
+                }
             }
         }
     }
 
     cbz.finish()?;
 
-    Ok(())
+    Ok(report)
 }
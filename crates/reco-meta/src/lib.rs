@@ -0,0 +1,382 @@
+use std::{
+    fs::File,
+    io::{self, Cursor, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use glob::{GlobError, PatternError};
+use image::ImageReader;
+use serde::Serialize;
+use tracing::debug;
+use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+use reco::{
+    BackendError, ComicInfo, LoadComicInfoError, PersistError, Reader as CbzReader,
+    ReaderOpenError as CbzReaderOpenError, TempOutput,
+    backend::{EntryName, NameEncoding, entry_names},
+    comic_info::{COMIC_INFO_PATH, ReadingDirection, parse_language},
+    reader::Options as ReaderOptions,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("glob pattern error: {0}")]
+    GlobPattern(#[from] PatternError),
+
+    #[error("glob error: {0}")]
+    Glob(#[from] GlobError),
+
+    #[error("cbz open error: {0}")]
+    CbzOpen(#[from] CbzReaderOpenError),
+
+    #[error("comic info load error: {0}")]
+    ComicInfoLoad(#[from] LoadComicInfoError),
+
+    #[error("archive entry read error: {0}")]
+    EntryRead(#[from] BackendError),
+
+    #[error("zip file error: {0}")]
+    ZipFile(#[from] zip::result::ZipError),
+
+    #[error("archive write error: {0}")]
+    ArchiveWrite(#[from] io::Error),
+
+    #[error("archive persist error: {0}")]
+    Persist(#[from] PersistError),
+
+    #[error("invalid {field} value: {value}")]
+    InvalidValue { field: &'static str, value: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown metadata field: {0}")]
+pub struct UnknownFieldError(String);
+
+/// A metadata field, either stored in the `ComicInfo.xml` of the archive or the zip comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Series,
+    Number,
+    Volume,
+    Writer,
+    Publisher,
+    /// An ISO 639 language code, e.g. `en` or `jpn`.
+    Language,
+    /// `ltr` or `rtl`.
+    ReadingDirection,
+    /// The zip comment.
+    Comment,
+    /// Any other `ComicInfo` element, e.g. `Summary` or `Genre`, its name is case-sensitive.
+    Other(String),
+}
+
+impl FromStr for Field {
+    type Err = UnknownFieldError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let field = match name.to_ascii_lowercase().as_str() {
+            "title" => Self::Title,
+            "series" => Self::Series,
+            "number" => Self::Number,
+            "volume" => Self::Volume,
+            "writer" => Self::Writer,
+            "publisher" => Self::Publisher,
+            "language" | "languageiso" => Self::Language,
+            "reading-direction" | "direction" | "manga" => Self::ReadingDirection,
+            "comment" => Self::Comment,
+            "pages" | "comicinfo" => return Err(UnknownFieldError(name.to_string())),
+            _ if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                Self::Other(name.to_string())
+            }
+            _ => return Err(UnknownFieldError(name.to_string())),
+        };
+
+        Ok(field)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Set(Field, String),
+    Unset(Field),
+}
+
+/// The length of the prefix of the images read to decode their dimensions, most headers fit in it
+/// (JPEG ones can be longer, because of their metadata, the image is then read entirely).
+const HEADER_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    changes: Vec<Change>,
+    name_encoding: NameEncoding,
+}
+
+impl Options {
+    /// The encoding of the zip entry names not flagged as UTF-8, detected by default.
+    #[must_use]
+    pub fn with_name_encoding(mut self, name_encoding: NameEncoding) -> Self {
+        self.name_encoding = name_encoding;
+        self
+    }
+
+    /// Changes are applied in order, a field can be set after being unset (and vice versa).
+    #[must_use]
+    pub fn with_change(mut self, change: Change) -> Self {
+        self.changes.push(change);
+        self
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Page {
+    pub name: String,
+    /// The image dimensions, if its header could be decoded.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Metadata {
+    pub path: PathBuf,
+    pub page_count: usize,
+    pub comment: Option<String>,
+    pub comic_info: Option<ComicInfo>,
+    pub pages: Vec<Page>,
+}
+
+/// Reads the metadata of all the archives matching the pattern, the entry names not flagged as
+/// UTF-8 being decoded with the encoding.
+pub fn inspect(pattern: &str, name_encoding: NameEncoding) -> Result<Vec<Metadata>, Error> {
+    glob::glob(pattern)?
+        .map(|path| read(&path?, name_encoding))
+        .collect()
+}
+
+pub fn read(path: &Path, name_encoding: NameEncoding) -> Result<Metadata, Error> {
+    let mut cbz_reader = CbzReader::try_open_with_options(
+        path,
+        ReaderOptions::default().with_name_encoding(name_encoding),
+    )?;
+    let comic_info = cbz_reader.comic_info()?;
+    let comment = comment(cbz_reader.comment());
+
+    let file_names = cbz_reader
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let mut pages = Vec::with_capacity(file_names.len());
+
+    for name in file_names {
+        let header = cbz_reader.read_prefix(&name, HEADER_LEN)?;
+        let dimensions = match dimensions(header) {
+            Some(dimensions) => Some(dimensions),
+            None => dimensions(cbz_reader.read(&name)?),
+        };
+        if dimensions.is_none() {
+            debug!("{name} dimensions can't be decoded");
+        }
+
+        pages.push(Page {
+            name,
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+        });
+    }
+
+    Ok(Metadata {
+        path: path.to_path_buf(),
+        page_count: pages.len(),
+        comment,
+        comic_info,
+        pages,
+    })
+}
+
+/// Applies the changes to all the archives matching the pattern, returning the edited paths.
+pub fn edit(pattern: &str, opts: &Options) -> Result<Vec<PathBuf>, Error> {
+    let mut edited = Vec::new();
+
+    for path in glob::glob(pattern)? {
+        let path = path?;
+        edit_archive(&path, opts)?;
+        edited.push(path);
+    }
+
+    Ok(edited)
+}
+
+/// Applies the changes to the archive (zip only), which is rewritten in place (entries are copied
+/// as is). The zip comment is kept as stored unless changed.
+pub fn edit_archive(path: &Path, opts: &Options) -> Result<(), Error> {
+    let mut cbz_reader = CbzReader::try_open_with_options(
+        path,
+        ReaderOptions::default().with_name_encoding(opts.name_encoding),
+    )?;
+    let mut comic_info = cbz_reader.comic_info()?.unwrap_or_default();
+    let raw_comment = cbz_reader.comment().to_vec();
+    let mut comment = comment(&raw_comment);
+    drop(cbz_reader);
+
+    let mut comment_changed = false;
+    for change in &opts.changes {
+        apply(change, &mut comic_info, &mut comment)?;
+        comment_changed |= matches!(
+            change,
+            Change::Set(Field::Comment, _) | Change::Unset(Field::Comment)
+        );
+    }
+    let comment = if comment_changed {
+        comment.unwrap_or_default().into_bytes()
+    } else {
+        raw_comment
+    };
+
+    let (file, output) = TempOutput::create(path, false)?;
+    rewrite(path, file, &comic_info, &comment)?;
+    output.persist()?;
+
+    Ok(())
+}
+
+/// Repairs the names of all the archives matching the pattern, returning the repaired paths.
+pub fn repair(pattern: &str, encoding: NameEncoding) -> Result<Vec<PathBuf>, Error> {
+    let mut repaired = Vec::new();
+
+    for path in glob::glob(pattern)? {
+        let path = path?;
+        if repair_archive(&path, encoding)? {
+            repaired.push(path);
+        }
+    }
+
+    Ok(repaired)
+}
+
+/// Rewrites the archive (zip only) in place with UTF-8 entry names, the names not flagged as UTF-8
+/// being decoded with the encoding (entries are copied as is). Returns whether the archive was
+/// rewritten, it isn't when all its names already are UTF-8.
+pub fn repair_archive(path: &Path, encoding: NameEncoding) -> Result<bool, Error> {
+    let names = entry_names(&mut ZipArchive::new(File::open(path)?)?, encoding)?;
+    if names.iter().all(EntryName::is_utf8) {
+        return Ok(false);
+    }
+
+    let (file, output) = TempOutput::create(path, false)?;
+    rewrite_names(path, file, &names)?;
+    output.persist()?;
+
+    Ok(true)
+}
+
+/// The dimensions of the image, if its header can be decoded.
+fn dimensions(buf: Vec<u8>) -> Option<(u32, u32)> {
+    ImageReader::new(Cursor::new(buf))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+}
+
+fn comment(comment: &[u8]) -> Option<String> {
+    let comment = String::from_utf8_lossy(comment);
+    let comment = comment.trim();
+
+    (!comment.is_empty()).then(|| comment.to_string())
+}
+
+fn apply(
+    change: &Change,
+    comic_info: &mut ComicInfo,
+    comment: &mut Option<String>,
+) -> Result<(), Error> {
+    let (field, value) = match change {
+        Change::Set(field, value) => (field, Some(value.as_str())),
+        Change::Unset(field) => (field, None),
+    };
+    let to_string = || value.map(ToString::to_string);
+
+    match field {
+        Field::Title => comic_info.title = to_string(),
+        Field::Series => comic_info.series = to_string(),
+        Field::Number => comic_info.number = to_string(),
+        Field::Writer => comic_info.writer = to_string(),
+        Field::Publisher => comic_info.publisher = to_string(),
+        Field::Comment => *comment = to_string(),
+        Field::Other(name) => comic_info.set_other(name, value),
+        Field::Volume => {
+            comic_info.volume = value
+                .map(|value| value.trim().parse().map_err(|_| invalid("volume", value)))
+                .transpose()?;
+        }
+        Field::Language => {
+            comic_info.language = value
+                .map(|value| parse_language(value.trim()).ok_or_else(|| invalid("language", value)))
+                .transpose()?;
+        }
+        Field::ReadingDirection => {
+            comic_info.reading_direction = value
+                .map(|value| match value.trim().to_ascii_lowercase().as_str() {
+                    "ltr" | "left-to-right" => Ok(ReadingDirection::LeftToRight),
+                    "rtl" | "right-to-left" => Ok(ReadingDirection::RightToLeft),
+                    _ => Err(invalid("reading direction", value)),
+                })
+                .transpose()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid(field: &'static str, value: &str) -> Error {
+    Error::InvalidValue {
+        field,
+        value: value.to_string(),
+    }
+}
+
+/// Writes the archive, with the metadata, to the file.
+fn rewrite(path: &Path, file: File, comic_info: &ComicInfo, comment: &[u8]) -> Result<(), Error> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut writer = ZipWriter::new(file);
+
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        if file.name().eq_ignore_ascii_case(COMIC_INFO_PATH) {
+            continue;
+        }
+
+        writer.raw_copy_file(file)?;
+    }
+
+    if !comic_info.is_empty() {
+        writer.start_file(COMIC_INFO_PATH, SimpleFileOptions::default())?;
+        writer.write_all(comic_info.to_xml().as_bytes())?;
+    }
+
+    writer.set_raw_comment(comment.into());
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes the archive, with the names, to the file.
+fn rewrite_names(path: &Path, file: File, names: &[EntryName]) -> Result<(), Error> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut writer = ZipWriter::new(file);
+
+    for (index, entry) in names.iter().enumerate() {
+        let file = archive.by_index_raw(index)?;
+        writer.raw_copy_file_rename(file, &entry.name)?;
+    }
+
+    writer.set_raw_comment(archive.comment().into());
+    writer.finish()?;
+
+    Ok(())
+}
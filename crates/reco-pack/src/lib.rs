@@ -1,25 +1,25 @@
-use std::{
-    io::{self, Seek, Write},
-    path::Path,
-};
+use std::{io, path::Path};
 
 use glob::{GlobError, PatternError};
 use image::ImageReader;
 use lexical_sort::{PathSort, natural_lexical_cmp};
-use tracing::error;
 
 use reco::{
-    ArchiveFinishError, CreateArchiveError, InsertionError as CbzInsertionError,
-    Writer as CbzWriter,
+    ArchiveFinishError, CreateArchiveError, ErrorPolicy, InsertionError as CbzInsertionError,
+    NoProgress, Pipeline, PipelineCreationError, Progress, Report, Writer as CbzWriter,
+    writer::{EncodedPage, Encoder, Options as WriterOptions},
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Options {
     autosplit: bool,
+    writer: WriterOptions,
+    jobs: usize,
+    error_policy: ErrorPolicy,
 }
 
 impl Options {
-    fn has_img_modifier(self) -> bool {
+    fn has_img_modifier(&self) -> bool {
         self.autosplit
     }
 }
@@ -27,7 +27,33 @@ impl Options {
 impl Options {
     #[must_use]
     pub fn new(autosplit: bool) -> Self {
-        Self { autosplit }
+        Self {
+            autosplit,
+            writer: WriterOptions::default(),
+            jobs: 0,
+            error_policy: ErrorPolicy::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_writer_options(mut self, writer: WriterOptions) -> Self {
+        self.writer = writer;
+        self
+    }
+
+    /// The number of threads images are decoded, transformed and encoded on, one per core if 0
+    /// (the default).
+    #[must_use]
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// What to do with the images which can't be read or inserted (skipped by default).
+    #[must_use]
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
     }
 }
 
@@ -53,32 +79,75 @@ pub enum Error {
 
     #[error("image decode error: {0}")]
     ImageDecode(image::error::ImageError),
+
+    #[error("pipeline creation error: {0}")]
+    PipelineCreation(#[from] PipelineCreationError),
+}
+
+pub fn pack(pattern: &str, path: &Path, opts: Options) -> Result<Report, Error> {
+    pack_with_progress(pattern, path, opts, &NoProgress)
 }
 
-pub fn pack(pattern: &str, path: &Path, opts: Options) -> Result<(), Error> {
+/// Packs the images, reporting each image packed to `progress` (an autosplit image is a single
+/// page done).
+pub fn pack_with_progress(
+    pattern: &str,
+    path: &Path,
+    opts: Options,
+    progress: &dyn Progress,
+) -> Result<Report, Error> {
     let mut paths = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
 
     paths.path_sort_unstable(natural_lexical_cmp);
 
-    let mut cbz = CbzWriter::create_from_path(path)?;
-
-    for path in paths {
-        if let Err(err) = insert_from_path(&mut cbz, &path, opts) {
-            error!("image insertion error: {err}");
-        }
-    }
+    let pipeline = Pipeline::new(opts.jobs)?;
+    let mut cbz = CbzWriter::create_from_path_with_options(path, opts.writer.clone())?;
+    let encoder = cbz.encoder();
+    progress.add_total(paths.len());
+    let mut report = Report::default();
+
+    pipeline.run(
+        paths,
+        |path| {
+            let res = encode_from_path(encoder, &path, &opts);
+            (path, res)
+        },
+        |(path, res)| {
+            progress.start_file(&path);
+            let res = res.and_then(|pages| {
+                pages.into_iter().try_fold(0, |len, page| {
+                    cbz.insert_encoded(&page)?;
+                    report.pages += 1;
+                    Ok::<_, Error>(len + page.len() as u64)
+                })
+            });
+            match res {
+                Ok(len) => progress.page_done(len),
+                Err(err) => {
+                    if report.record(opts.error_policy, path.display(), err, true)? {
+                        cbz.insert_placeholder()?;
+                        report.pages += 1;
+                    }
+                    progress.page_done(0);
+                }
+            }
+
+            Ok::<_, Error>(())
+        },
+    )?;
 
     cbz.finish()?;
 
-    Ok(())
+    Ok(report)
 }
 
+/// The pages of the image, run on the pipeline threads.
 #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-fn insert_from_path(
-    cbz: &mut CbzWriter<impl Write + Seek>,
+fn encode_from_path(
+    encoder: Encoder,
     path: &Path,
-    opts: Options,
-) -> Result<(), Error> {
+    opts: &Options,
+) -> Result<Vec<EncodedPage>, Error> {
     if opts.has_img_modifier() {
         let mut img = ImageReader::open(path)
             .map_err(Error::ImageOpen)?
@@ -99,14 +168,17 @@ fn insert_from_path(
                 height,
             );
 
-            cbz.insert_image_as_jpeg(&left_img, 75)?;
-            cbz.insert_image_as_jpeg(&right_img, 75)?;
-
-            return Ok(());
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            return Ok(vec![
+                encoder
+                    .encode_image(&left_img)?
+                    .with_name(format!("{stem}-1")),
+                encoder
+                    .encode_image(&right_img)?
+                    .with_name(format!("{stem}-2")),
+            ]);
         }
     }
 
-    cbz.insert_from_path(path)?;
-
-    Ok(())
+    Ok(vec![encoder.encode_path(path)?])
 }
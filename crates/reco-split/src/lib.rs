@@ -0,0 +1,298 @@
+use std::{
+    fs::{self, File},
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use lexical_sort::{StringSort, natural_lexical_cmp};
+use tracing::warn;
+
+use reco::{
+    ArchiveFinishError, BackendError, ComicInfo, CreateArchiveError,
+    InsertionError as CbzInsertionError, LoadComicInfoError, PersistError, Reader as CbzReader,
+    ReaderOpenError as CbzReaderOpenError, TempOutput, Writer as CbzWriter,
+    comic_info::PageInfo,
+    reader::Options as ReaderOptions,
+    writer::{Options as WriterOptions, split_entry_name},
+};
+
+/// Where the archive is split, each part holding at least one page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Strategy {
+    /// Parts of (at most) this many pages.
+    PageCount(usize),
+    /// Parts of (at most) this many bytes of images, a bigger image being a part on its own.
+    Size(u64),
+    /// A part per top-level chapter (see `Reader::chapters`), the pages before the first chapter
+    /// being part of it.
+    Chapters,
+    /// Parts starting at these page numbers (starting at 1), and at the first page.
+    At(Vec<usize>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    strategy: Strategy,
+    reader: ReaderOptions,
+    writer: WriterOptions,
+}
+
+impl Options {
+    #[must_use]
+    pub fn new(strategy: Strategy) -> Self {
+        Self {
+            strategy,
+            reader: ReaderOptions::default(),
+            writer: WriterOptions::default(),
+        }
+    }
+
+    /// How the pages are ordered before being split.
+    #[must_use]
+    pub fn with_reader_options(mut self, reader: ReaderOptions) -> Self {
+        self.reader = reader;
+        self
+    }
+
+    /// The parts format and encoding, their title is their file stem.
+    #[must_use]
+    pub fn with_writer_options(mut self, writer: WriterOptions) -> Self {
+        self.writer = writer;
+        self
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("cbz open error: {0}")]
+    CbzOpen(#[from] CbzReaderOpenError),
+
+    #[error("archive entry read error: {0}")]
+    EntryRead(#[from] BackendError),
+
+    #[error("comic info load error: {0}")]
+    ComicInfoLoad(#[from] LoadComicInfoError),
+
+    #[error("archive creation error: {0}")]
+    ArchiveCreation(#[from] CreateArchiveError),
+
+    #[error("archive creation error: {0}")]
+    ArchiveFinish(#[from] ArchiveFinishError),
+
+    #[error("cbz insertion error: {0}")]
+    CbzInsertion(#[from] CbzInsertionError),
+
+    #[error("part creation error: {0}")]
+    PartCreation(#[from] io::Error),
+
+    #[error("{0} already exists")]
+    AlreadyExists(PathBuf),
+
+    #[error("part persist error: {0}")]
+    PartPersist(#[from] PersistError),
+}
+
+/// Splits the archive into parts written in the output directory, named after the archive file
+/// stem and numbered from 1, e.g. `Berserk 01.cbz`. Pages are copied as is (without being
+/// decompressed from a zip) unless re-encoded. The `ComicInfo.xml` and the zip comment are copied
+/// in every part, its pages being those of the part. The parts are only moved to the output
+/// directory once all written, any page error failing the split. Returns the parts paths.
+pub fn split(path: &Path, output: &Path, opts: &Options) -> Result<Vec<PathBuf>, Error> {
+    let mut cbz_reader = CbzReader::try_open_with_options(path, opts.reader)?;
+    let comic_info = cbz_reader.comic_info()?;
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    fs::create_dir_all(output)?;
+    let parts = write_parts(&mut cbz_reader, comic_info.as_ref(), output, &stem, opts)?;
+
+    let width = parts.len().to_string().len().max(2);
+    let paths = (1..=parts.len())
+        .map(|number| {
+            output.join(format!(
+                "{stem} {number:0width$}.{}",
+                opts.writer.format().extension()
+            ))
+        })
+        .collect::<Vec<_>>();
+    // Checked before any part is moved, persisting fails anyway if one is created meanwhile
+    if opts.writer.no_clobber()
+        && let Some(path) = paths.iter().find(|path| path.exists())
+    {
+        return Err(Error::AlreadyExists(path.clone()));
+    }
+
+    for (part, path) in parts.into_iter().zip(&paths) {
+        part.persist_as(path)?;
+    }
+
+    Ok(paths)
+}
+
+/// Writes the parts to temporary files in the output directory.
+fn write_parts(
+    cbz_reader: &mut CbzReader,
+    comic_info: Option<&ComicInfo>,
+    output: &Path,
+    stem: &str,
+    opts: &Options,
+) -> Result<Vec<TempOutput>, Error> {
+    let file_names = cbz_reader
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let starts = starts(cbz_reader, &opts.strategy, file_names.len());
+    let comment = cbz_reader.comment().to_vec();
+
+    let mut parts = Vec::new();
+    let mut part: Option<(CbzWriter<File>, usize)> = None;
+    let mut part_size = 0;
+
+    for (index, file_name) in file_names.iter().enumerate() {
+        // Zip entries are copied, the others are read once
+        let compressed_size = cbz_reader
+            .zip_entry(file_name)?
+            .map(|file| file.compressed_size());
+        let (size, buf) = match compressed_size {
+            Some(size) => (size, None),
+            None => {
+                let buf = cbz_reader.read(file_name)?;
+                (buf.len() as u64, Some(buf))
+            }
+        };
+
+        let new_part = match opts.strategy {
+            Strategy::Size(max) => part_size > 0 && part_size + size > max,
+            _ => starts.binary_search(&index).is_ok(),
+        };
+        if new_part || part.is_none() {
+            if let Some((writer, start)) = part.take() {
+                finish_part(writer, comic_info, &comment, &file_names, start..index)?;
+            }
+
+            // Named once the part count is known
+            let (file, tmp) = TempOutput::create(&output.join(stem), opts.writer.no_clobber())?;
+            parts.push(tmp);
+            let writer_opts = opts
+                .writer
+                .clone()
+                .with_title(format!("{stem} {}", parts.len()));
+
+            part = Some((CbzWriter::with_options(file, writer_opts), index));
+            part_size = 0;
+        }
+
+        if let Some((writer, _)) = &mut part {
+            match buf {
+                Some(buf) => {
+                    let (name, ext) = split_entry_name(file_name);
+                    let mut page = writer.encoder().encode_buf(ext, buf)?;
+                    if let Some(name) = name {
+                        page = page.with_name(name);
+                    }
+                    writer.insert_encoded(&page)?;
+                }
+                None => {
+                    writer
+                        .copy_raw(cbz_reader, file_name)
+                        .map_err(|err| match err {
+                            CbzInsertionError::EntryRead(err) => Error::EntryRead(err),
+                            err => err.into(),
+                        })?;
+                }
+            }
+        }
+        part_size += size;
+    }
+
+    if let Some((writer, start)) = part {
+        finish_part(
+            writer,
+            comic_info,
+            &comment,
+            &file_names,
+            start..file_names.len(),
+        )?;
+    }
+
+    Ok(parts)
+}
+
+/// The spine indices parts start at, sorted (unused when splitting by size).
+fn starts(cbz_reader: &CbzReader, strategy: &Strategy, len: usize) -> Vec<usize> {
+    let mut starts = match strategy {
+        Strategy::PageCount(count) => (0..len).step_by((*count).max(1)).collect(),
+        Strategy::Size(_) => Vec::new(),
+        Strategy::Chapters => {
+            let chapters = cbz_reader.chapters();
+            if chapters.is_empty() {
+                warn!("the archive has no chapters, it isn't split");
+            }
+
+            // The first part starts at the first page, whatever the first chapter
+            chapters
+                .iter()
+                .skip(1)
+                .map(|chapter| chapter.index)
+                .collect()
+        }
+        Strategy::At(pages) => pages
+            .iter()
+            .filter(|&&page| page > 0 && page <= len)
+            .map(|page| page - 1)
+            .collect(),
+    };
+    starts.sort_unstable();
+    starts.dedup();
+
+    starts
+}
+
+fn finish_part(
+    mut writer: CbzWriter<File>,
+    comic_info: Option<&ComicInfo>,
+    comment: &[u8],
+    file_names: &[String],
+    range: Range<usize>,
+) -> Result<(), Error> {
+    writer.set_comic_info(
+        comic_info.map(|comic_info| part_comic_info(comic_info, file_names, range)),
+    );
+    writer.set_comment(comment);
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// The `ComicInfo` of the part, with the pages it holds. Page indices refer to the naturally sorted
+/// entries, and so to the spine for the part whose entries are named in order.
+fn part_comic_info(
+    comic_info: &ComicInfo,
+    file_names: &[String],
+    range: Range<usize>,
+) -> ComicInfo {
+    let mut natural = file_names.to_vec();
+    natural.string_sort_unstable(natural_lexical_cmp);
+
+    let mut part = comic_info.clone();
+    part.pages = comic_info
+        .pages
+        .iter()
+        .filter_map(|page| {
+            let name = natural.get(page.image)?;
+            let index = file_names.iter().position(|other| other == name)?;
+
+            range.contains(&index).then(|| PageInfo {
+                image: index - range.start,
+                ..page.clone()
+            })
+        })
+        .collect();
+    part.pages.sort_by_key(|page| page.image);
+
+    part
+}
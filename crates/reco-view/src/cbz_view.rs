@@ -1,4 +1,4 @@
-use std::{fs::File, path::Path, sync::Arc};
+use std::{path::Path, sync::Arc};
 
 use egui::{Key, TextureHandle, Vec2};
 use egui_router::Route;
@@ -6,24 +6,45 @@ use image::DynamicImage;
 use tracing::error;
 
 use crate::Result;
-use reco::Reader as CbzReader;
+use reco::{Reader as CbzReader, reader::Options as ReaderOptions, toc};
 
 pub struct CbzView {
-    cbz: CbzReader<File>,
+    cbz: CbzReader,
+    /// The table of contents entries, indented by depth, with their page index.
+    chapters: Vec<(String, usize)>,
     current_img: Arc<egui::ColorImage>,
     current_texture: TextureHandle,
 }
 
 impl CbzView {
     pub fn try_from_path(ctx: &egui::Context, path: &Path) -> Result<Self> {
-        let mut cbz = CbzReader::try_open(path)?;
+        Self::try_from_path_with_options(ctx, path, ReaderOptions::default())
+    }
+
+    pub fn try_from_path_with_options(
+        ctx: &egui::Context,
+        path: &Path,
+        opts: ReaderOptions,
+    ) -> Result<Self> {
+        let mut cbz = CbzReader::try_open_with_options(path, opts)?;
         let current_img = Arc::new(convert_img(&cbz.load_current_img()?));
 
         let current_texture =
             ctx.load_texture("image", current_img.clone(), egui::TextureOptions::LINEAR);
 
+        let chapters = toc::flatten(cbz.chapters())
+            .into_iter()
+            .map(|(depth, chapter)| {
+                (
+                    format!("{}{}", "    ".repeat(depth), chapter.title),
+                    chapter.index,
+                )
+            })
+            .collect();
+
         Ok(Self {
             cbz,
+            chapters,
             current_img,
             current_texture,
         })
@@ -39,6 +60,16 @@ impl CbzView {
         self.load_current_img(ctx);
     }
 
+    fn go_to_prev_chapter(&mut self, ctx: &egui::Context) {
+        self.cbz.go_to_prev_chapter();
+        self.load_current_img(ctx);
+    }
+
+    fn go_to_next_chapter(&mut self, ctx: &egui::Context) {
+        self.cbz.go_to_next_chapter();
+        self.load_current_img(ctx);
+    }
+
     fn go_to_index(&mut self, ctx: &egui::Context, index: usize) {
         if self.cbz.spine().current_index() == index {
             return;
@@ -76,6 +107,14 @@ impl<S> Route<S> for CbzView {
             self.go_to_next_index(&ctx);
         }
 
+        if ctx.input(|i| i.key_pressed(Key::PageUp)) {
+            self.go_to_prev_chapter(&ctx);
+        }
+
+        if ctx.input(|i| i.key_pressed(Key::PageDown)) {
+            self.go_to_next_chapter(&ctx);
+        }
+
         ui.vertical_centered(|ui| {
             let available = ui.available_size();
             let (width, height) =
@@ -113,11 +152,51 @@ impl<S> Route<S> for CbzView {
                 .text("Page");
 
                 ui.add(slider);
+
+                if !self.chapters.is_empty() {
+                    self.chapters_ui(ui, &ctx);
+                }
             });
         });
     }
 }
 
+impl CbzView {
+    fn chapters_ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        if ui.button("Previous chapter").clicked() {
+            self.go_to_prev_chapter(ctx);
+        }
+
+        let current_chapter = self.cbz.current_chapter().map(|chapter| chapter.index);
+        let mut selected = None;
+
+        egui::ComboBox::from_id_salt("chapters")
+            .selected_text(
+                self.cbz
+                    .current_chapter()
+                    .map_or("Chapters", |chapter| chapter.title.as_str()),
+            )
+            .show_ui(ui, |ui| {
+                for (label, index) in &self.chapters {
+                    if ui
+                        .selectable_label(current_chapter == Some(*index), label)
+                        .clicked()
+                    {
+                        selected = Some(*index);
+                    }
+                }
+            });
+
+        if let Some(index) = selected {
+            self.go_to_index(ctx, index);
+        }
+
+        if ui.button("Next chapter").clicked() {
+            self.go_to_next_chapter(ctx);
+        }
+    }
+}
+
 fn convert_img(img: &DynamicImage) -> egui::ColorImage {
     if let DynamicImage::ImageRgb8(rgb) = img {
         egui::ColorImage::from_rgb([rgb.width() as usize, rgb.height() as usize], rgb.as_raw())
@@ -4,6 +4,7 @@ pub use cbz_view::CbzView;
 use egui::Key;
 use egui_router::Route;
 pub use errors::{Error, Result};
+use reco::reader::Options as ReaderOptions;
 
 mod cbz_view;
 mod errors;
@@ -34,6 +35,11 @@ impl eframe::App for App {
 }
 
 pub fn view(path: &Path) -> Result<()> {
+    view_with_options(path, ReaderOptions::default())
+}
+
+/// Opens the archive with these options, e.g. the encoding of its entry names.
+pub fn view_with_options(path: &Path, opts: ReaderOptions) -> Result<()> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([960.0, 720.0])
@@ -47,7 +53,7 @@ pub fn view(path: &Path) -> Result<()> {
         Box::new(|cc| {
             let cbz_view = App {
                 fullscreen: false,
-                cbz_view: CbzView::try_from_path(&cc.egui_ctx, path)?,
+                cbz_view: CbzView::try_from_path_with_options(&cc.egui_ctx, path, opts)?,
             };
             Ok(Box::new(cbz_view))
         }),
@@ -0,0 +1,165 @@
+//! Read access to the archive formats comics come in, CBZ (zip), CBR (rar), CB7 (7z) and CBT
+//! (tar, optionally gzipped), and to plain directories of images.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+pub use dir::DirBackend;
+pub use rar::RarBackend;
+pub use sevenz::SevenZBackend;
+pub use tar::TarBackend;
+pub use zip::{EntryName, NameEncoding, ZipBackend, entry_names};
+
+use ::zip::read::ZipFile;
+
+use crate::errors::BackendError;
+
+mod dir;
+mod rar;
+mod sevenz;
+mod tar;
+mod zip;
+
+/// File extensions of the supported archives.
+pub static EXTENSIONS: &[&str] = &["cbz", "cbr", "cb7", "cbt", "zip", "rar", "7z", "tar", "tgz"];
+
+/// Whether the path has the extension of a supported archive.
+#[must_use]
+pub fn has_archive_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            EXTENSIONS
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// A seekable reader, as zip archives are read from.
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// The reader of zip archives, boxed to keep the backend (and its entries) non-generic.
+pub type BoxedReader = Box<dyn ReadSeek>;
+
+/// Read access to the file entries of an archive.
+pub trait Backend: Send {
+    /// The file entries names (directories excluded), in archive order.
+    fn file_names(&self) -> &[String];
+
+    /// Reads an entry entirely.
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, BackendError>;
+
+    /// Reads (at most) the first `len` bytes of an entry.
+    ///
+    /// Backends able to stop reading an entry (e.g. to sniff its format) should override it, the
+    /// entry is read entirely otherwise.
+    fn read_prefix(&mut self, name: &str, len: usize) -> Result<Vec<u8>, BackendError> {
+        let mut buf = self.read(name)?;
+        buf.truncate(len);
+
+        Ok(buf)
+    }
+
+    /// Reads (at most) the first `len` bytes of every entry, in archive order.
+    ///
+    /// Backends without random access (e.g. solid archives) should override it to read the
+    /// archive once.
+    fn read_prefixes(&mut self, len: usize) -> Result<Vec<(String, Vec<u8>)>, BackendError> {
+        let file_names = self.file_names().to_vec();
+
+        file_names
+            .into_iter()
+            .map(|name| {
+                let buf = self.read_prefix(&name, len)?;
+                Ok((name, buf))
+            })
+            .collect()
+    }
+
+    /// The archive comment, empty if the format has none.
+    fn comment(&self) -> &[u8] {
+        &[]
+    }
+
+    /// The entry as stored in a zip archive, to be copied without being decompressed. `None` for
+    /// the other formats.
+    fn zip_entry(&mut self, _name: &str) -> Result<Option<ZipFile<'_, BoxedReader>>, BackendError> {
+        Ok(None)
+    }
+}
+
+/// Opens the archive, its format being guessed from its content, or the directory.
+pub fn open(path: &Path) -> Result<Box<dyn Backend>, BackendError> {
+    open_with_name_encoding(path, NameEncoding::default())
+}
+
+/// Opens the archive, decoding the zip entry names not flagged as UTF-8 with the encoding.
+pub fn open_with_name_encoding(
+    path: &Path,
+    name_encoding: NameEncoding,
+) -> Result<Box<dyn Backend>, BackendError> {
+    if path.is_dir() {
+        return Ok(Box::new(DirBackend::open(path)?));
+    }
+
+    let mut file = File::open(path)?;
+
+    // The rar library only reads from paths
+    if infer::archive::is_rar(&read_signature(&mut file)?) {
+        return Ok(Box::new(RarBackend::open(path)?));
+    }
+
+    from_reader_with_name_encoding(file, name_encoding)
+}
+
+/// Reads a zip, 7z or tar archive, its format being guessed from its content. A gzipped file is
+/// expected to be a tarball.
+pub fn from_reader<R>(reader: R) -> Result<Box<dyn Backend>, BackendError>
+where
+    R: Read + Seek + Send + 'static,
+{
+    from_reader_with_name_encoding(reader, NameEncoding::default())
+}
+
+/// Reads the archive, decoding the zip entry names not flagged as UTF-8 with the encoding.
+pub fn from_reader_with_name_encoding<R>(
+    mut reader: R,
+    name_encoding: NameEncoding,
+) -> Result<Box<dyn Backend>, BackendError>
+where
+    R: Read + Seek + Send + 'static,
+{
+    let signature = read_signature(&mut reader)?;
+
+    if infer::archive::is_7z(&signature) {
+        return Ok(Box::new(SevenZBackend::new(reader)?));
+    }
+
+    if infer::archive::is_tar(&signature) || infer::archive::is_gz(&signature) {
+        let gzip = infer::archive::is_gz(&signature);
+        return Ok(Box::new(TarBackend::new(reader, gzip)?));
+    }
+
+    if infer::archive::is_rar(&signature) {
+        return Err(BackendError::UnsupportedReader);
+    }
+
+    Ok(Box::new(ZipBackend::with_name_encoding(
+        Box::new(reader),
+        name_encoding,
+    )?))
+}
+
+/// The first bytes of the archive, enough to hold the tar magic (at offset 257).
+fn read_signature<R: Read + Seek>(reader: &mut R) -> Result<Vec<u8>, BackendError> {
+    let mut signature = Vec::with_capacity(262);
+    reader.by_ref().take(262).read_to_end(&mut signature)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    Ok(signature)
+}
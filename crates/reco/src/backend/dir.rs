@@ -0,0 +1,86 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use tracing::debug;
+use walkdir::WalkDir;
+
+use super::Backend;
+use crate::errors::BackendError;
+
+/// A plain directory, whose files (in all its sub-directories) are the entries.
+pub struct DirBackend {
+    root: PathBuf,
+    file_names: Vec<String>,
+    /// The file paths (relative to the root) by entry name, which is lossy for non UTF-8 paths.
+    paths: HashMap<String, PathBuf>,
+}
+
+impl DirBackend {
+    pub fn open(root: &Path) -> Result<Self, BackendError> {
+        let mut file_names = Vec::new();
+        let mut paths = HashMap::new();
+
+        for entry in WalkDir::new(root).sort_by_file_name() {
+            let entry = entry.map_err(io::Error::from)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let Ok(path) = entry.path().strip_prefix(root) else {
+                continue;
+            };
+            let name = path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            if paths.contains_key(&name) {
+                debug!(
+                    "{} has the same name as another file, skipping",
+                    path.display()
+                );
+                continue;
+            }
+            paths.insert(name.clone(), path.to_path_buf());
+            file_names.push(name);
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            file_names,
+            paths,
+        })
+    }
+
+    /// The path of the file, only listed files are read so a name can't escape the directory.
+    fn path(&self, name: &str) -> Result<PathBuf, BackendError> {
+        self.paths
+            .get(name)
+            .map(|path| self.root.join(path))
+            .ok_or_else(|| BackendError::EntryNotFound(name.to_string()))
+    }
+}
+
+impl Backend for DirBackend {
+    fn file_names(&self) -> &[String] {
+        &self.file_names
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, BackendError> {
+        Ok(fs::read(self.path(name)?)?)
+    }
+
+    fn read_prefix(&mut self, name: &str, len: usize) -> Result<Vec<u8>, BackendError> {
+        let mut buf = Vec::with_capacity(len);
+        File::open(self.path(name)?)?
+            .take(len as u64)
+            .read_to_end(&mut buf)?;
+
+        Ok(buf)
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+
+use unrar::Archive;
+
+use super::Backend;
+use crate::errors::BackendError;
+
+/// RAR archives can only be read sequentially, each read goes through the archive from its
+/// start. Their comment isn't read (unrar doesn't expose it).
+pub struct RarBackend {
+    path: PathBuf,
+    file_names: Vec<String>,
+}
+
+impl RarBackend {
+    pub fn open(path: &Path) -> Result<Self, BackendError> {
+        let mut file_names = Vec::new();
+        for header in Archive::new(path).open_for_listing()? {
+            let header = header?;
+            if header.is_file() {
+                file_names.push(entry_name(&header.filename));
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file_names,
+        })
+    }
+}
+
+impl Backend for RarBackend {
+    fn file_names(&self) -> &[String] {
+        &self.file_names
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, BackendError> {
+        let mut archive = Archive::new(&self.path).open_for_processing()?;
+
+        while let Some(entry) = archive.read_header()? {
+            if entry.entry().is_file() && entry_name(&entry.entry().filename) == name {
+                return Ok(entry.read()?.0);
+            }
+
+            archive = entry.skip()?;
+        }
+
+        Err(BackendError::EntryNotFound(name.to_string()))
+    }
+
+    fn read_prefixes(&mut self, len: usize) -> Result<Vec<(String, Vec<u8>)>, BackendError> {
+        let mut archive = Archive::new(&self.path).open_for_processing()?;
+        let mut prefixes = Vec::with_capacity(self.file_names.len());
+
+        while let Some(entry) = archive.read_header()? {
+            if !entry.entry().is_file() {
+                archive = entry.skip()?;
+                continue;
+            }
+
+            let name = entry_name(&entry.entry().filename);
+            let (mut buf, next) = entry.read()?;
+            buf.truncate(len);
+
+            prefixes.push((name, buf));
+            archive = next;
+        }
+
+        Ok(prefixes)
+    }
+}
+
+/// Entry names use `/` as separator, whatever the platform.
+fn entry_name(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
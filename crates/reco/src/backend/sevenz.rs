@@ -0,0 +1,59 @@
+use std::io::{Read, Seek};
+
+use sevenz_rust2::{ArchiveReader, Password};
+
+use super::Backend;
+use crate::errors::BackendError;
+
+pub struct SevenZBackend<R: Read + Seek> {
+    archive: ArchiveReader<R>,
+    file_names: Vec<String>,
+}
+
+impl<R: Read + Seek> SevenZBackend<R> {
+    pub fn new(reader: R) -> Result<Self, BackendError> {
+        let archive = ArchiveReader::new(reader, Password::empty())?;
+        let file_names = archive
+            .archive()
+            .files
+            .iter()
+            .filter(|entry| !entry.is_directory)
+            .map(|entry| entry.name.clone())
+            .collect();
+
+        Ok(Self {
+            archive,
+            file_names,
+        })
+    }
+}
+
+impl<R: Read + Seek + Send> Backend for SevenZBackend<R> {
+    fn file_names(&self) -> &[String] {
+        &self.file_names
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, BackendError> {
+        Ok(self.archive.read_file(name)?)
+    }
+
+    /// Decodes the archive once, reading every entry from the start of a solid block is slow.
+    fn read_prefixes(&mut self, len: usize) -> Result<Vec<(String, Vec<u8>)>, BackendError> {
+        let mut prefixes = Vec::with_capacity(self.file_names.len());
+
+        self.archive.for_each_entries(|entry, reader| {
+            // Entries are read entirely, the decoder expects them to be
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf)?;
+
+            if !entry.is_directory {
+                buf.truncate(len);
+                prefixes.push((entry.name.clone(), buf));
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(prefixes)
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use flate2::read::GzDecoder;
+use tar::Archive;
+
+use super::Backend;
+use crate::errors::BackendError;
+
+struct Entry {
+    name: String,
+    /// The entry data position in the (decompressed) tarball.
+    offset: u64,
+    size: u64,
+}
+
+/// Tarballs, optionally gzipped. Entries of a plain tarball are read in place. A gzipped one is
+/// decompressed as a stream kept between reads, so entries read in archive order are decompressed
+/// once, the stream being restarted to read an entry located before its position.
+pub struct TarBackend<R> {
+    source: Source<R>,
+    entries: Vec<Entry>,
+    indexes: HashMap<String, usize>,
+    file_names: Vec<String>,
+}
+
+enum Source<R> {
+    Plain(R),
+    /// The decompressed stream (only taken while it's restarted), and its position.
+    Gzip(Option<GzDecoder<R>>, u64),
+}
+
+impl<R: Read + Seek> TarBackend<R> {
+    pub fn new(mut reader: R, gzip: bool) -> Result<Self, BackendError> {
+        let mut entries = Vec::new();
+
+        for entry in Archive::new(stream(&mut reader, gzip)?).entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let name = entry.path()?.to_string_lossy().replace('\\', "/");
+            entries.push(Entry {
+                name: name.strip_prefix("./").unwrap_or(&name).to_string(),
+                offset: entry.raw_file_position(),
+                size: entry.size(),
+            });
+        }
+
+        let file_names = entries
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect::<Vec<_>>();
+        let indexes = file_names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.clone(), index))
+            .collect();
+
+        let source = if gzip {
+            reader.seek(SeekFrom::Start(0))?;
+            Source::Gzip(Some(GzDecoder::new(reader)), 0)
+        } else {
+            Source::Plain(reader)
+        };
+
+        Ok(Self {
+            source,
+            entries,
+            indexes,
+            file_names,
+        })
+    }
+
+    fn entry(&self, name: &str) -> Result<&Entry, BackendError> {
+        self.indexes
+            .get(name)
+            .map(|&index| &self.entries[index])
+            .ok_or_else(|| BackendError::EntryNotFound(name.to_string()))
+    }
+
+    /// Reads (at most) `len` bytes at this position of the (decompressed) tarball.
+    fn read_at(&mut self, offset: u64, len: u64) -> Result<Vec<u8>, BackendError> {
+        let mut buf = Vec::with_capacity(usize::try_from(len).unwrap_or_default());
+
+        match &mut self.source {
+            Source::Plain(reader) => {
+                reader.seek(SeekFrom::Start(offset))?;
+                reader.take(len).read_to_end(&mut buf)?;
+            }
+            Source::Gzip(decoder, position) => {
+                if *position > offset
+                    && let Some(stream) = decoder
+                {
+                    stream.get_mut().seek(SeekFrom::Start(0))?;
+                    *decoder = decoder
+                        .take()
+                        .map(|stream| GzDecoder::new(stream.into_inner()));
+                    *position = 0;
+                }
+                let stream = decoder
+                    .as_mut()
+                    .ok_or_else(|| io::Error::other("gzip stream unavailable"))?;
+
+                // The stream is restarted on the next read if this one fails
+                let start = *position;
+                *position = u64::MAX;
+                io::copy(&mut stream.by_ref().take(offset - start), &mut io::sink())?;
+                stream.take(len).read_to_end(&mut buf)?;
+                *position = offset + buf.len() as u64;
+            }
+        }
+
+        Ok(buf)
+    }
+}
+
+impl<R: Read + Seek + Send> Backend for TarBackend<R> {
+    fn file_names(&self) -> &[String] {
+        &self.file_names
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, BackendError> {
+        let entry = self.entry(name)?;
+
+        self.read_at(entry.offset, entry.size)
+    }
+
+    fn read_prefix(&mut self, name: &str, len: usize) -> Result<Vec<u8>, BackendError> {
+        let entry = self.entry(name)?;
+
+        self.read_at(entry.offset, entry.size.min(len as u64))
+    }
+}
+
+/// The tarball content from its start.
+fn stream<R: Read + Seek>(reader: &mut R, gzip: bool) -> io::Result<Box<dyn Read + '_>> {
+    reader.seek(SeekFrom::Start(0))?;
+
+    Ok(if gzip {
+        Box::new(GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    })
+}
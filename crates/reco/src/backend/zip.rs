@@ -0,0 +1,312 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+    str::FromStr,
+};
+
+use chardetng::EncodingDetector;
+use encoding_rs::{BIG5, EUC_JP, EUC_KR, Encoding, GBK, SHIFT_JIS};
+use zip::{ZipArchive, read::ZipFile};
+
+use super::{Backend, BoxedReader};
+use crate::errors::{BackendError, NameEncodingParseError};
+
+/// The encodings of the legacy (e.g. Japanese or Chinese) names detected by
+/// [`NameEncoding::Auto`], the first one being preferred when they fit as well.
+static DETECTED_ENCODINGS: &[&Encoding] = &[SHIFT_JIS, GBK, BIG5, EUC_KR, EUC_JP];
+
+/// The encoding of the zip entry names not flagged as UTF-8, which zip archivers write in the
+/// system encoding (e.g. Shift-JIS on Japanese Windows).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameEncoding {
+    /// UTF-8 if the names are valid UTF-8, a Japanese, Chinese or Korean encoding if one is
+    /// detected (e.g. Shift-JIS or GBK), CP437 (the zip standard encoding) otherwise.
+    #[default]
+    Auto,
+    Cp437,
+    Encoding(&'static Encoding),
+}
+
+/// Parses `auto`, `cp437` or an encoding label, e.g. `shift_jis`, `gbk` or `utf-8`.
+impl FromStr for NameEncoding {
+    type Err = NameEncodingParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "cp437" | "ibm437" => Ok(Self::Cp437),
+            label => Encoding::for_label(label.as_bytes())
+                .map(Self::Encoding)
+                .ok_or_else(|| NameEncodingParseError(s.to_string())),
+        }
+    }
+}
+
+/// An entry name, as decoded and as stored.
+#[derive(Debug, Clone)]
+pub struct EntryName {
+    pub name: String,
+    pub raw: Vec<u8>,
+    pub is_file: bool,
+    /// Whether the name is flagged as UTF-8 (or ASCII), whatever it is decoded with.
+    utf8: bool,
+}
+
+impl EntryName {
+    /// Whether the name is flagged as UTF-8 (or is ASCII), and so readable by any tool. Names not
+    /// flagged are not, even when they are valid UTF-8.
+    #[must_use]
+    pub fn is_utf8(&self) -> bool {
+        self.utf8
+    }
+}
+
+/// The names of the archive entries, by index, the names not flagged as UTF-8 being decoded with
+/// the encoding.
+pub fn entry_names<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    encoding: NameEncoding,
+) -> Result<Vec<EntryName>, BackendError> {
+    let mut names = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        // The names not flagged are decoded as CP437, which only leaves ASCII unchanged
+        names.push(EntryName {
+            name: file.name().to_string(),
+            raw: file.name_raw().to_vec(),
+            is_file: file.is_file(),
+            utf8: file.name().as_bytes() == file.name_raw(),
+        });
+    }
+
+    // Flagged as UTF-8 or ASCII, CP437 decoded otherwise
+    let legacy = names
+        .iter()
+        .filter(|name| !name.is_utf8())
+        .map(|name| name.raw.as_slice())
+        .collect::<Vec<_>>();
+    let encoding = match encoding {
+        NameEncoding::Auto => detect(&legacy),
+        NameEncoding::Cp437 => None,
+        NameEncoding::Encoding(encoding) => Some(encoding),
+    };
+
+    if let Some(encoding) = encoding {
+        for name in names.iter_mut().filter(|name| !name.is_utf8()) {
+            name.name = encoding
+                .decode_without_bom_handling(&name.raw)
+                .0
+                .into_owned();
+        }
+    }
+
+    Ok(names)
+}
+
+/// The encoding of the legacy names, `None` for CP437.
+fn detect(names: &[&[u8]]) -> Option<&'static Encoding> {
+    if names.is_empty() {
+        return None;
+    }
+
+    if names.iter().all(|name| str::from_utf8(name).is_ok()) {
+        return Some(encoding_rs::UTF_8);
+    }
+
+    let mut detector = EncodingDetector::new();
+    for name in names {
+        detector.feed(name, false);
+        detector.feed(b" ", false);
+    }
+    detector.feed(&[], true);
+    let guess = detector.guess(None, false);
+    if DETECTED_ENCODINGS.contains(&guess) && score(guess, names).is_some() {
+        return Some(guess);
+    }
+
+    // Names are often too short for the detector
+    DETECTED_ENCODINGS
+        .iter()
+        .filter_map(|&encoding| Some((encoding, score(encoding, names)?)))
+        .filter(|&(_, score)| score > 0)
+        .rev()
+        .max_by_key(|&(_, score)| score)
+        .map(|(encoding, _)| encoding)
+}
+
+/// How much the names decoded look like Japanese, Chinese or Korean, `None` if they can't be
+/// decoded.
+fn score(encoding: &'static Encoding, names: &[&[u8]]) -> Option<i64> {
+    let mut score = 0;
+    for name in names {
+        let name = encoding.decode_without_bom_handling_and_without_replacement(name)?;
+        for c in name.chars().filter(|c| !c.is_ascii()) {
+            score += match c {
+                // Hiragana and katakana
+                '\u{3040}'..='\u{30ff}' => 2,
+                // CJK ideographs, hangul syllables and CJK punctuation
+                '\u{4e00}'..='\u{9fff}' | '\u{ac00}'..='\u{d7a3}' | '\u{3000}'..='\u{303f}' => 1,
+                // Anything else, e.g. half-width katakana, is unlikely in names
+                _ => -1,
+            };
+        }
+    }
+
+    Some(score)
+}
+
+pub struct ZipBackend {
+    archive: ZipArchive<BoxedReader>,
+    file_names: Vec<String>,
+    /// The entries indexes by decoded name.
+    indexes: HashMap<String, usize>,
+}
+
+impl ZipBackend {
+    pub fn new(reader: BoxedReader) -> Result<Self, BackendError> {
+        Self::with_name_encoding(reader, NameEncoding::default())
+    }
+
+    pub fn with_name_encoding(
+        reader: BoxedReader,
+        encoding: NameEncoding,
+    ) -> Result<Self, BackendError> {
+        let mut archive = ZipArchive::new(reader)?;
+
+        let mut file_names = Vec::with_capacity(archive.len());
+        let mut indexes = HashMap::with_capacity(archive.len());
+        for (index, entry) in entry_names(&mut archive, encoding)?.into_iter().enumerate() {
+            if entry.is_file {
+                indexes.insert(entry.name.clone(), index);
+                file_names.push(entry.name);
+            }
+        }
+
+        Ok(Self {
+            archive,
+            file_names,
+            indexes,
+        })
+    }
+
+    fn index(&self, name: &str) -> Result<usize, BackendError> {
+        self.indexes
+            .get(name)
+            .copied()
+            .ok_or_else(|| BackendError::EntryNotFound(name.to_string()))
+    }
+}
+
+impl Backend for ZipBackend {
+    fn file_names(&self) -> &[String] {
+        &self.file_names
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, BackendError> {
+        let mut file = self.archive.by_index(self.index(name)?)?;
+
+        let mut buf = Vec::with_capacity(usize::try_from(file.size()).unwrap_or_default());
+        file.read_to_end(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    /// Only decompresses the prefixes.
+    fn read_prefix(&mut self, name: &str, len: usize) -> Result<Vec<u8>, BackendError> {
+        let file = self.archive.by_index(self.index(name)?)?;
+
+        let mut buf = Vec::with_capacity(len);
+        file.take(len as u64).read_to_end(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    fn comment(&self) -> &[u8] {
+        self.archive.comment()
+    }
+
+    fn zip_entry(&mut self, name: &str) -> Result<Option<ZipFile<'_, BoxedReader>>, BackendError> {
+        let index = self.index(name)?;
+
+        Ok(Some(self.archive.by_index_raw(index)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let names = [
+            "第01話/001.jpg",
+            "第01話/002.png",
+            "第02話/003.jpg",
+            "表紙.jpg",
+        ]
+        .map(|name| SHIFT_JIS.encode(name).0.into_owned());
+        let names = names.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        assert_eq!(detect(&names), Some(SHIFT_JIS));
+
+        let names =
+            ["第一章/封面.jpg", "第一章/目录.png"].map(|name| GBK.encode(name).0.into_owned());
+        let names = names.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        assert_eq!(detect(&names), Some(GBK));
+
+        assert_eq!(detect(&["café.jpg".as_bytes()]), Some(encoding_rs::UTF_8));
+        assert_eq!(detect(&[b"caf\x82.jpg"]), None);
+    }
+
+    /// A zip archive of empty stored entries, whose names aren't flagged as UTF-8.
+    fn unflagged_zip(names: &[&str]) -> Vec<u8> {
+        let (mut buf, mut central) = (Vec::new(), Vec::new());
+        for name in names {
+            let offset = u32::try_from(buf.len()).unwrap_or_default();
+            let len = u16::try_from(name.len()).unwrap_or_default();
+            buf.extend(0x0403_4b50_u32.to_le_bytes());
+            buf.extend(20_u16.to_le_bytes());
+            buf.extend([0; 20]);
+            buf.extend(len.to_le_bytes());
+            buf.extend([0; 2]);
+            buf.extend(name.as_bytes());
+
+            central.extend(0x0201_4b50_u32.to_le_bytes());
+            central.extend([20, 0, 20, 0]);
+            central.extend([0; 20]);
+            central.extend(len.to_le_bytes());
+            central.extend([0; 12]);
+            central.extend(offset.to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+
+        let count = u16::try_from(names.len()).unwrap_or_default();
+        let (size, offset) = (central.len(), buf.len());
+        buf.extend(central);
+        buf.extend(0x0605_4b50_u32.to_le_bytes());
+        buf.extend([0; 4]);
+        buf.extend(count.to_le_bytes());
+        buf.extend(count.to_le_bytes());
+        buf.extend(u32::try_from(size).unwrap_or_default().to_le_bytes());
+        buf.extend(u32::try_from(offset).unwrap_or_default().to_le_bytes());
+        buf.extend([0; 2]);
+
+        buf
+    }
+
+    #[test]
+    fn test_entry_names() -> Result<(), BackendError> {
+        let buf = unflagged_zip(&["cover.jpg", "café.jpg"]);
+        let names = entry_names(
+            &mut ZipArchive::new(std::io::Cursor::new(buf))?,
+            NameEncoding::Auto,
+        )?;
+
+        assert_eq!(names[0].name, "cover.jpg");
+        assert!(names[0].is_utf8());
+        // Decoded as UTF-8, but still not flagged as such
+        assert_eq!(names[1].name, "café.jpg");
+        assert!(!names[1].is_utf8());
+
+        Ok(())
+    }
+}
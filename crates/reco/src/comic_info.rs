@@ -0,0 +1,470 @@
+//! `ComicInfo.xml` metadata, as read by Komga, Kavita, and most comic servers and readers.
+//!
+//! Only the most common fields are modelled, the other ones (and the modelled ones that can't be
+//! parsed) are kept as is so that reading then writing metadata doesn't lose anything.
+
+use std::{borrow::Cow, fmt::Write as _, str::FromStr};
+
+use isolang::Language;
+use quick_xml::{
+    Reader as XmlReader,
+    escape::{escape, unescape},
+    events::{BytesStart, Event},
+};
+use serde::{Serialize, Serializer};
+
+use crate::errors::ComicInfoParseError;
+
+pub static COMIC_INFO_PATH: &str = "ComicInfo.xml";
+
+/// Elements order, as defined by the `ComicInfo` v2 schema.
+static ELEMENTS_ORDER: &[&str] = &[
+    "Title",
+    "Series",
+    "Number",
+    "Count",
+    "Volume",
+    "AlternateSeries",
+    "AlternateNumber",
+    "AlternateCount",
+    "Summary",
+    "Notes",
+    "Year",
+    "Month",
+    "Day",
+    "Writer",
+    "Penciller",
+    "Inker",
+    "Colorist",
+    "Letterer",
+    "CoverArtist",
+    "Editor",
+    "Publisher",
+    "Imprint",
+    "Genre",
+    "Web",
+    "PageCount",
+    "LanguageISO",
+    "Format",
+    "BlackAndWhite",
+    "Manga",
+    "Characters",
+    "Teams",
+    "Locations",
+    "ScanInformation",
+    "StoryArc",
+    "SeriesGroup",
+    "AgeRating",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadingDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PageType {
+    FrontCover,
+    InnerCover,
+    Roundup,
+    Story,
+    Advertisement,
+    Editorial,
+    Letters,
+    Preview,
+    BackCover,
+    Other,
+    Deleted,
+}
+
+impl PageType {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::FrontCover => "FrontCover",
+            Self::InnerCover => "InnerCover",
+            Self::Roundup => "Roundup",
+            Self::Story => "Story",
+            Self::Advertisement => "Advertisement",
+            Self::Editorial => "Editorial",
+            Self::Letters => "Letters",
+            Self::Preview => "Preview",
+            Self::BackCover => "BackCover",
+            Self::Other => "Other",
+            Self::Deleted => "Deleted",
+        }
+    }
+}
+
+impl FromStr for PageType {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        [
+            Self::FrontCover,
+            Self::InnerCover,
+            Self::Roundup,
+            Self::Story,
+            Self::Advertisement,
+            Self::Editorial,
+            Self::Letters,
+            Self::Preview,
+            Self::BackCover,
+            Self::Other,
+            Self::Deleted,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str().eq_ignore_ascii_case(value))
+        .ok_or(())
+    }
+}
+
+/// A page of the archive, `image` being its index in the spine.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PageInfo {
+    pub image: usize,
+    #[serde(rename = "type")]
+    pub kind: Option<PageType>,
+    /// Whether the image is a spread, i.e. 2 pages wide.
+    pub double_page: bool,
+    pub image_size: Option<u64>,
+    pub image_width: Option<u32>,
+    pub image_height: Option<u32>,
+    pub bookmark: Option<String>,
+}
+
+impl PageInfo {
+    #[must_use]
+    pub fn new(image: usize) -> Self {
+        Self {
+            image,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ComicInfo {
+    pub title: Option<String>,
+    pub series: Option<String>,
+    /// The issue or chapter number, which is not necessarily an integer (e.g. `12.5`).
+    pub number: Option<String>,
+    pub volume: Option<i32>,
+    pub writer: Option<String>,
+    pub publisher: Option<String>,
+    #[serde(serialize_with = "serialize_language")]
+    pub language: Option<Language>,
+    pub reading_direction: Option<ReadingDirection>,
+    pub pages: Vec<PageInfo>,
+    /// Elements not modelled above, with their (escaped) content.
+    #[serde(serialize_with = "serialize_others")]
+    others: Vec<(String, String)>,
+}
+
+impl ComicInfo {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Elements not modelled by this type, with their unescaped content (nested elements, if any,
+    /// are kept as is).
+    pub fn others(&self) -> impl Iterator<Item = (&str, Cow<'_, str>)> {
+        unescaped(&self.others)
+    }
+
+    /// Sets (or removes if `value` is `None`) an element that is not modelled by this type.
+    pub fn set_other(&mut self, name: &str, value: Option<&str>) {
+        self.others.retain(|(other, _)| other != name);
+
+        if let Some(value) = value {
+            self.others
+                .push((name.to_string(), escape(value).into_owned()));
+        }
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self, ComicInfoParseError> {
+        let mut reader = XmlReader::from_str(xml.trim_start_matches('\u{feff}'));
+        reader.config_mut().trim_text(true);
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(start) if start.local_name().into_inner() == "ComicInfo" => break,
+                Event::Empty(start) if start.local_name().into_inner() == "ComicInfo" => {
+                    return Ok(Self::default());
+                }
+                Event::Eof => return Err(ComicInfoParseError::MissingRoot),
+                _ => {}
+            }
+        }
+
+        let mut comic_info = Self::default();
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(start) if start.local_name().into_inner() == "Pages" => {
+                    comic_info.pages = read_pages(&mut reader)?;
+                }
+                Event::Start(start) => {
+                    let name = start.local_name().into_inner().to_string();
+                    let content = reader.read_text(start.name())?;
+                    comic_info.set(name, content.trim().to_string());
+                }
+                Event::End(_) | Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(comic_info)
+    }
+
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut elements = self.others.clone();
+        let mut push = |name: &str, value: Option<Cow<'_, str>>| {
+            if let Some(value) = value {
+                elements.push((name.to_string(), escape(value).into_owned()));
+            }
+        };
+
+        push("Title", self.title.as_deref().map(Cow::from));
+        push("Series", self.series.as_deref().map(Cow::from));
+        push("Number", self.number.as_deref().map(Cow::from));
+        push(
+            "Volume",
+            self.volume.map(|volume| volume.to_string().into()),
+        );
+        push("Writer", self.writer.as_deref().map(Cow::from));
+        push("Publisher", self.publisher.as_deref().map(Cow::from));
+        push(
+            "LanguageISO",
+            self.language.map(|language| language_code(language).into()),
+        );
+        push(
+            "Manga",
+            self.reading_direction.map(|direction| match direction {
+                ReadingDirection::LeftToRight => "No".into(),
+                ReadingDirection::RightToLeft => "YesAndRightToLeft".into(),
+            }),
+        );
+
+        // Unknown elements are written last
+        elements.sort_by_key(|(name, _)| {
+            ELEMENTS_ORDER
+                .iter()
+                .position(|element| element == name)
+                .unwrap_or(ELEMENTS_ORDER.len())
+        });
+
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+        );
+
+        for (name, content) in elements {
+            let _ = writeln!(xml, "  <{name}>{content}</{name}>");
+        }
+
+        if !self.pages.is_empty() {
+            xml.push_str("  <Pages>\n");
+            for page in &self.pages {
+                let _ = writeln!(xml, "    <Page{} />", page_attributes(page));
+            }
+            xml.push_str("  </Pages>\n");
+        }
+
+        xml.push_str("</ComicInfo>\n");
+
+        xml
+    }
+
+    /// Sets a field from its element, keeping the raw content of unknown or invalid elements.
+    fn set(&mut self, name: String, content: String) {
+        let text = || unescape(&content).map(Cow::into_owned).ok();
+
+        let parsed = match name.as_str() {
+            "Title" => text().map(|title| self.title = Some(title)),
+            "Series" => text().map(|series| self.series = Some(series)),
+            "Number" => text().map(|number| self.number = Some(number)),
+            "Writer" => text().map(|writer| self.writer = Some(writer)),
+            "Publisher" => text().map(|publisher| self.publisher = Some(publisher)),
+            "Volume" => text()
+                .and_then(|volume| volume.parse().ok())
+                .map(|volume| self.volume = Some(volume)),
+            "LanguageISO" => text()
+                .and_then(|code| parse_language(&code))
+                .map(|language| self.language = Some(language)),
+            "Manga" => match text().as_deref() {
+                Some("YesAndRightToLeft") => {
+                    self.reading_direction = Some(ReadingDirection::RightToLeft);
+                    Some(())
+                }
+                Some("No") => {
+                    self.reading_direction = Some(ReadingDirection::LeftToRight);
+                    Some(())
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if parsed.is_none() {
+            self.others.retain(|(other, _)| other != &name);
+            self.others.push((name, content));
+        }
+    }
+}
+
+/// Parses ISO 639-1 or 639-3 codes, optionally followed by a region (e.g. `en-US`).
+#[must_use]
+pub fn parse_language(code: &str) -> Option<Language> {
+    let code = code.split(['-', '_']).next()?.to_ascii_lowercase();
+
+    Language::from_639_1(&code).or_else(|| Language::from_639_3(&code))
+}
+
+/// The ISO 639-1 code of the language, or its 639-3 code if it has none.
+#[must_use]
+pub fn language_code(language: Language) -> &'static str {
+    language.to_639_1().unwrap_or(language.to_639_3())
+}
+
+// The signature is imposed by serde `serialize_with`
+#[expect(clippy::ref_option, clippy::trivially_copy_pass_by_ref)]
+fn serialize_language<S: Serializer>(
+    language: &Option<Language>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    language.map(language_code).serialize(serializer)
+}
+
+/// Other elements are serialized as a map of their unescaped content.
+fn serialize_others<S: Serializer>(
+    others: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(unescaped(others))
+}
+
+fn unescaped(elements: &[(String, String)]) -> impl Iterator<Item = (&str, Cow<'_, str>)> {
+    elements.iter().map(|(name, content)| {
+        let content = unescape(content).unwrap_or(Cow::Borrowed(content.as_str()));
+        (name.as_str(), content)
+    })
+}
+
+fn read_pages(reader: &mut XmlReader<&[u8]>) -> Result<Vec<PageInfo>, ComicInfoParseError> {
+    let mut pages = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Empty(start) if start.local_name().into_inner() == "Page" => {
+                pages.extend(parse_page(&start));
+            }
+            Event::Start(start) if start.local_name().into_inner() == "Page" => {
+                pages.extend(parse_page(&start));
+                reader.read_to_end(start.name())?;
+            }
+            Event::End(_) | Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(pages)
+}
+
+/// Parses the attributes of a `Page` element, pages without a valid `Image` index are ignored.
+fn parse_page(start: &BytesStart<'_>) -> Option<PageInfo> {
+    let mut image = None;
+    let mut page = PageInfo::default();
+
+    for attribute in start.attributes().flatten() {
+        let value = unescape(&attribute.value)
+            .map_or_else(|_| attribute.value.to_string(), Cow::into_owned);
+
+        match attribute.key.local_name().into_inner() {
+            "Image" => image = value.parse().ok(),
+            "Type" => page.kind = value.parse().ok(),
+            "DoublePage" => page.double_page = value.eq_ignore_ascii_case("true"),
+            "ImageSize" => page.image_size = value.parse().ok(),
+            "ImageWidth" => page.image_width = value.parse().ok(),
+            "ImageHeight" => page.image_height = value.parse().ok(),
+            "Bookmark" => page.bookmark = Some(value),
+            _ => {}
+        }
+    }
+
+    image.map(|image| PageInfo { image, ..page })
+}
+
+fn page_attributes(page: &PageInfo) -> String {
+    let mut attributes = format!(" Image=\"{}\"", page.image);
+
+    if let Some(kind) = page.kind {
+        let _ = write!(attributes, " Type=\"{}\"", kind.as_str());
+    }
+    if page.double_page {
+        attributes.push_str(" DoublePage=\"true\"");
+    }
+    if let Some(image_size) = page.image_size {
+        let _ = write!(attributes, " ImageSize=\"{image_size}\"");
+    }
+    if let Some(image_width) = page.image_width {
+        let _ = write!(attributes, " ImageWidth=\"{image_width}\"");
+    }
+    if let Some(image_height) = page.image_height {
+        let _ = write!(attributes, " ImageHeight=\"{image_height}\"");
+    }
+    if let Some(bookmark) = &page.bookmark {
+        let _ = write!(attributes, " Bookmark=\"{}\"", escape(bookmark.as_str()));
+    }
+
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() -> Result<(), ComicInfoParseError> {
+        let xml = r#"<?xml version="1.0"?>
+<ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Series>Tom &amp; Jerry</Series>
+  <Number>12.5</Number>
+  <Volume>3</Volume>
+  <Summary>A <b>bold</b> summary</Summary>
+  <LanguageISO>ja-JP</LanguageISO>
+  <Manga>YesAndRightToLeft</Manga>
+  <Pages>
+    <Page Image="0" Type="FrontCover" ImageWidth="800" />
+    <Page Image="1" DoublePage="True" />
+    <Page Type="Story" />
+  </Pages>
+</ComicInfo>"#;
+
+        let comic_info = ComicInfo::from_xml(xml)?;
+
+        assert_eq!(comic_info.series.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(comic_info.number.as_deref(), Some("12.5"));
+        assert_eq!(comic_info.volume, Some(3));
+        assert_eq!(comic_info.language, Some(Language::Jpn));
+        assert_eq!(
+            comic_info.reading_direction,
+            Some(ReadingDirection::RightToLeft)
+        );
+        assert_eq!(comic_info.pages.len(), 2);
+        assert_eq!(comic_info.pages[0].kind, Some(PageType::FrontCover));
+        assert_eq!(comic_info.pages[0].image_width, Some(800));
+        assert!(comic_info.pages[1].double_page);
+        assert_eq!(
+            comic_info.others().collect::<Vec<_>>(),
+            [("Summary", "A <b>bold</b> summary".into())]
+        );
+
+        assert_eq!(ComicInfo::from_xml(&comic_info.to_xml())?, comic_info);
+
+        Ok(())
+    }
+}
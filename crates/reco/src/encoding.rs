@@ -0,0 +1,218 @@
+//! Encoding of the images inserted in an archive.
+
+use std::io::Cursor;
+
+use image::{
+    DynamicImage, ImageError, ImageFormat as ImageCrateFormat,
+    codecs::{
+        avif::AvifEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+        webp::WebPEncoder,
+    },
+    error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
+};
+use jpeg_encoder::{ColorType as JpegColorType, Encoder as JpegEncoder, SamplingFactor};
+
+pub static DEFAULT_QUALITY: u8 = 75;
+
+/// Speed of the AVIF encoder, from 1 (slowest, smallest files) to 10 (fastest).
+static AVIF_SPEED: u8 = 6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageFormat {
+    #[default]
+    Jpeg,
+    /// Always lossless, the quality is ignored.
+    Png,
+    /// Always lossless, lossy WebP encoding is not supported (yet), the quality is ignored.
+    WebP,
+    Avif,
+}
+
+impl ImageFormat {
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpeg",
+            Self::Png => "png",
+            Self::WebP => "webp",
+            Self::Avif => "avif",
+        }
+    }
+
+    /// Whether the format can encode images without any loss.
+    #[must_use]
+    pub fn supports_lossless(self) -> bool {
+        matches!(self, Self::Png | Self::WebP)
+    }
+
+    /// Whether the format is encoded with loss, i.e. whether the quality is used.
+    #[must_use]
+    pub fn supports_lossy(self) -> bool {
+        matches!(self, Self::Jpeg | Self::Avif)
+    }
+
+    fn hint(self) -> ImageFormatHint {
+        ImageFormatHint::Exact(match self {
+            Self::Jpeg => ImageCrateFormat::Jpeg,
+            Self::Png => ImageCrateFormat::Png,
+            Self::WebP => ImageCrateFormat::WebP,
+            Self::Avif => ImageCrateFormat::Avif,
+        })
+    }
+}
+
+/// Chroma subsampling ratio, only used by the JPEG encoder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    #[default]
+    Yuv420,
+    Yuv422,
+    Yuv444,
+}
+
+impl From<ChromaSubsampling> for SamplingFactor {
+    fn from(chroma_subsampling: ChromaSubsampling) -> Self {
+        match chroma_subsampling {
+            ChromaSubsampling::Yuv420 => Self::R_4_2_0,
+            ChromaSubsampling::Yuv422 => Self::R_4_2_2,
+            ChromaSubsampling::Yuv444 => Self::R_4_4_4,
+        }
+    }
+}
+
+/// How images are encoded before being inserted in an archive.
+///
+/// Defaults to JPEG, quality 75, with 4:2:0 chroma subsampling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingOptions {
+    format: ImageFormat,
+    quality: u8,
+    lossless: bool,
+    chroma_subsampling: ChromaSubsampling,
+}
+
+impl Default for EncodingOptions {
+    fn default() -> Self {
+        Self::new(ImageFormat::default())
+    }
+}
+
+impl EncodingOptions {
+    #[must_use]
+    pub fn new(format: ImageFormat) -> Self {
+        Self {
+            format,
+            quality: DEFAULT_QUALITY,
+            lossless: false,
+            chroma_subsampling: ChromaSubsampling::default(),
+        }
+    }
+
+    /// The encoding quality, from 1 to 100.
+    #[must_use]
+    pub fn with_quality(mut self, quality: u8) -> Self {
+        self.quality = quality.clamp(1, 100);
+        self
+    }
+
+    /// Requires a lossless encoding, encoding fails if the format doesn't support it.
+    #[must_use]
+    pub fn with_lossless(mut self, lossless: bool) -> Self {
+        self.lossless = lossless;
+        self
+    }
+
+    #[must_use]
+    pub fn with_chroma_subsampling(mut self, chroma_subsampling: ChromaSubsampling) -> Self {
+        self.chroma_subsampling = chroma_subsampling;
+        self
+    }
+
+    #[must_use]
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    #[must_use]
+    pub fn quality(&self) -> u8 {
+        self.quality
+    }
+
+    #[must_use]
+    pub fn lossless(&self) -> bool {
+        self.lossless
+    }
+
+    #[must_use]
+    pub fn chroma_subsampling(&self) -> ChromaSubsampling {
+        self.chroma_subsampling
+    }
+
+    pub fn encode(&self, img: &DynamicImage) -> Result<Vec<u8>, ImageError> {
+        if self.lossless && !self.format.supports_lossless() {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    self.format.hint(),
+                    UnsupportedErrorKind::GenericFeature("lossless encoding".to_string()),
+                ),
+            ));
+        }
+
+        let mut buf = Cursor::new(Vec::new());
+
+        match self.format {
+            ImageFormat::Jpeg => self.encode_jpeg(img, buf.get_mut())?,
+            ImageFormat::Png => {
+                let encoder = PngEncoder::new_with_quality(
+                    &mut buf,
+                    CompressionType::Default,
+                    FilterType::Adaptive,
+                );
+                img.write_with_encoder(encoder)?;
+            }
+            ImageFormat::WebP => {
+                let encoder = WebPEncoder::new_lossless(&mut buf);
+                with_8bit_channels(img).write_with_encoder(encoder)?;
+            }
+            ImageFormat::Avif => {
+                let encoder =
+                    AvifEncoder::new_with_speed_quality(&mut buf, AVIF_SPEED, self.quality);
+                with_8bit_channels(img).write_with_encoder(encoder)?;
+            }
+        }
+
+        Ok(buf.into_inner())
+    }
+
+    fn encode_jpeg(self, img: &DynamicImage, buf: &mut Vec<u8>) -> Result<(), ImageError> {
+        let to_image_error =
+            |err| ImageError::Encoding(EncodingError::new(self.format.hint(), err));
+        let (width, height) = (
+            u16::try_from(img.width()).map_err(to_image_error)?,
+            u16::try_from(img.height()).map_err(to_image_error)?,
+        );
+
+        let mut encoder = JpegEncoder::new(buf, self.quality);
+        encoder.set_sampling_factor(self.chroma_subsampling.into());
+
+        // Jpeg has no alpha channel, transparent images are encoded as is, without their alpha
+        let res = if img.color().has_color() {
+            encoder.encode(&img.to_rgb8(), width, height, JpegColorType::Rgb)
+        } else {
+            encoder.encode(&img.to_luma8(), width, height, JpegColorType::Luma)
+        };
+
+        res.map_err(|err| ImageError::Encoding(EncodingError::new(self.format.hint(), err)))
+    }
+}
+
+/// WebP and AVIF encoders only support 8 bits per channel images.
+fn with_8bit_channels(img: &DynamicImage) -> DynamicImage {
+    match (img.color().has_color(), img.color().has_alpha()) {
+        (true, true) => DynamicImage::ImageRgba8(img.to_rgba8()),
+        (true, false) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        (false, true) => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        (false, false) => DynamicImage::ImageLuma8(img.to_luma8()),
+    }
+}
@@ -0,0 +1,547 @@
+//! Fixed-layout EPUB 3 packaging.
+//!
+//! An EPUB is a zip archive too, so [`Writer`](crate::Writer) keeps writing images into its
+//! `ZipWriter` and delegates the EPUB specific parts (container, one XHTML document per image,
+//! OPF package and navigation document) to the [`Package`] below.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Write as _,
+    hash::{BuildHasher, Hasher},
+    io::{Cursor, Seek, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::{ImageError, ImageFormat, ImageReader};
+use tracing::debug;
+use zip::{
+    CompressionMethod, ZipWriter,
+    result::{ZipError, ZipResult},
+    write::SimpleFileOptions,
+};
+
+use crate::errors::InsertionError;
+
+pub static MIMETYPE: &str = "application/epub+zip";
+
+static CONTAINER_PATH: &str = "META-INF/container.xml";
+static PACKAGE_PATH: &str = "OEBPS/content.opf";
+static NAV_PATH: &str = "OEBPS/nav.xhtml";
+static DEFAULT_TITLE: &str = "Untitled";
+
+/// The image types reading systems must support, see
+/// <https://www.w3.org/TR/epub-33/#sec-core-media-types>. Other images are converted to PNG.
+static CORE_MEDIA_TYPES: &[&str] = &[
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/svg+xml",
+    "image/webp",
+];
+
+#[derive(Debug)]
+struct Page {
+    image_href: String,
+    document_href: String,
+    mime: &'static str,
+    width: u32,
+    height: u32,
+}
+
+/// Keeps track of the pages written so far and writes the EPUB structure around them.
+#[derive(Debug, Default)]
+pub struct Package {
+    title: Option<String>,
+    started: bool,
+    pages: Vec<Page>,
+}
+
+impl Package {
+    #[must_use]
+    pub fn new(title: Option<String>) -> Self {
+        Self {
+            title,
+            started: false,
+            pages: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Writes the image and its XHTML page, the image is expected to be a valid image buffer.
+    pub fn insert_page<W>(
+        &mut self,
+        archive: &mut ZipWriter<W>,
+        buf: &[u8],
+    ) -> Result<(), InsertionError>
+    where
+        W: Write + Seek,
+    {
+        let Some(format) =
+            infer::get(buf).and_then(|kind| ImageFormat::from_mime_type(kind.mime_type()))
+        else {
+            return Err(InsertionError::InvalidFormat);
+        };
+        let (width, height) = ImageReader::with_format(Cursor::new(buf), format)
+            .into_dimensions()
+            .map_err(InsertionError::ImageDecode)?;
+
+        self.insert_page_with_dimensions(archive, buf, width, height)
+    }
+
+    /// Same as [`Package::insert_page`], for images whose dimensions are already known. Images
+    /// which aren't of a core media type (e.g. BMP or TIFF) are converted to PNG, and rejected
+    /// when they can't be decoded (e.g. AVIF).
+    pub fn insert_page_with_dimensions<W>(
+        &mut self,
+        archive: &mut ZipWriter<W>,
+        buf: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<(), InsertionError>
+    where
+        W: Write + Seek,
+    {
+        let Some(kind) = infer::get(buf) else {
+            return Err(InsertionError::InvalidFormat);
+        };
+
+        let converted;
+        let (buf, mime, ext) = if CORE_MEDIA_TYPES.contains(&kind.mime_type()) {
+            (buf, kind.mime_type(), kind.extension())
+        } else {
+            debug!("converting a {} page to png", kind.mime_type());
+            converted = to_png(buf, kind.mime_type())?;
+            (converted.as_slice(), "image/png", "png")
+        };
+
+        self.start(archive).map_err(InsertionError::FileCreation)?;
+
+        let index = self.pages.len();
+        let page = Page {
+            image_href: format!("images/{index:0>5}.{ext}"),
+            document_href: format!("pages/{index:0>5}.xhtml"),
+            mime,
+            width,
+            height,
+        };
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        archive
+            .start_file(format!("OEBPS/{}", page.image_href), options)
+            .map_err(InsertionError::FileCreation)?;
+        archive.write_all(buf).map_err(InsertionError::FileWrite)?;
+
+        let options = SimpleFileOptions::default();
+        archive
+            .start_file(format!("OEBPS/{}", page.document_href), options)
+            .map_err(InsertionError::FileCreation)?;
+        archive
+            .write_all(page_document(index, &page).as_bytes())
+            .map_err(InsertionError::FileWrite)?;
+
+        self.pages.push(page);
+
+        Ok(())
+    }
+
+    /// Writes the package document and the navigation document.
+    pub fn finish<W>(mut self, archive: &mut ZipWriter<W>) -> ZipResult<()>
+    where
+        W: Write + Seek,
+    {
+        self.start(archive)?;
+
+        let options = SimpleFileOptions::default();
+        archive.start_file(PACKAGE_PATH, options)?;
+        archive
+            .write_all(self.package_document().as_bytes())
+            .map_err(ZipError::Io)?;
+
+        archive.start_file(NAV_PATH, options)?;
+        archive
+            .write_all(self.nav_document().as_bytes())
+            .map_err(ZipError::Io)?;
+
+        Ok(())
+    }
+
+    /// The `mimetype` entry must be the first one of the archive, and must not be compressed.
+    fn start<W>(&mut self, archive: &mut ZipWriter<W>) -> ZipResult<()>
+    where
+        W: Write + Seek,
+    {
+        if self.started {
+            return Ok(());
+        }
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        archive.start_file("mimetype", options)?;
+        archive
+            .write_all(MIMETYPE.as_bytes())
+            .map_err(ZipError::Io)?;
+
+        archive.start_file(CONTAINER_PATH, SimpleFileOptions::default())?;
+        archive
+            .write_all(container_document().as_bytes())
+            .map_err(ZipError::Io)?;
+
+        self.started = true;
+
+        Ok(())
+    }
+
+    fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(DEFAULT_TITLE)
+    }
+
+    fn package_document(&self) -> String {
+        let mut manifest = String::new();
+        let mut spine = String::new();
+
+        for (index, page) in self.pages.iter().enumerate() {
+            let properties = if index == 0 {
+                r#" properties="cover-image""#
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                manifest,
+                r#"    <item id="image-{index:0>5}" href="{}" media-type="{}"{properties}/>"#,
+                page.image_href, page.mime,
+            );
+            let _ = writeln!(
+                manifest,
+                r#"    <item id="page-{index:0>5}" href="{}" media-type="application/xhtml+xml"/>"#,
+                page.document_href,
+            );
+            let _ = writeln!(spine, r#"    <itemref idref="page-{index:0>5}"/>"#);
+        }
+
+        let cover = if self.pages.is_empty() {
+            String::new()
+        } else {
+            "\n    <meta name=\"cover\" content=\"image-00000\"/>".to_string()
+        };
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" prefix="rendition: http://www.idpf.org/vocab/rendition/#">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">urn:uuid:{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>und</dc:language>
+    <meta property="dcterms:modified">{modified}</meta>
+    <meta property="rendition:layout">pre-paginated</meta>
+    <meta property="rendition:orientation">auto</meta>
+    <meta property="rendition:spread">landscape</meta>{cover}
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#,
+            identifier = random_uuid(),
+            title = escape_xml(self.title()),
+            modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+        )
+    }
+
+    fn nav_document(&self) -> String {
+        let mut page_list = String::new();
+
+        for (index, page) in self.pages.iter().enumerate() {
+            let _ = writeln!(
+                page_list,
+                r#"        <li><a href="{}">{}</a></li>"#,
+                page.document_href,
+                index + 1,
+            );
+        }
+
+        let start = self
+            .pages
+            .first()
+            .map(|page| {
+                format!(
+                    "        <li><a href=\"{}\">{}</a></li>\n",
+                    page.document_href,
+                    escape_xml(self.title()),
+                )
+            })
+            .unwrap_or_default();
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+  <head>
+    <title>{title}</title>
+  </head>
+  <body>
+    <nav epub:type="toc" id="toc">
+      <ol>
+{start}      </ol>
+    </nav>
+    <nav epub:type="page-list" hidden="">
+      <ol>
+{page_list}      </ol>
+    </nav>
+  </body>
+</html>
+"#,
+            title = escape_xml(self.title()),
+        )
+    }
+}
+
+/// Re-encodes the image to PNG, as long as it can be decoded.
+fn to_png(buf: &[u8], mime: &'static str) -> Result<Vec<u8>, InsertionError> {
+    let Some(format) = ImageFormat::from_mime_type(mime) else {
+        return Err(InsertionError::EpubMediaType(mime));
+    };
+
+    let image = image::load_from_memory_with_format(buf, format).map_err(|err| match err {
+        ImageError::Unsupported(_) => InsertionError::EpubMediaType(mime),
+        err => InsertionError::ImageDecode(err),
+    })?;
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(InsertionError::ImageEncode)?;
+
+    Ok(png)
+}
+
+fn container_document() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="{PACKAGE_PATH}" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#
+    )
+}
+
+fn page_document(index: usize, page: &Page) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+  <head>
+    <title>{number}</title>
+    <meta name="viewport" content="width={width}, height={height}"/>
+    <style>html, body {{ margin: 0; padding: 0; }} img {{ display: block; width: 100%; height: 100%; }}</style>
+  </head>
+  <body>
+    <img src="../{image_href}" alt="{number}"/>
+  </body>
+</html>
+"#,
+        number = index + 1,
+        width = page.width,
+        height = page.height,
+        image_href = page.image_href,
+    )
+}
+
+pub(crate) fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// A random (version 4) uuid, only used as the unique identifier of the publication.
+fn random_uuid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+
+    let mut bytes = [0u8; 16];
+    for (i, chunk) in bytes.chunks_mut(8).enumerate() {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_usize(i);
+        chunk.copy_from_slice(&hasher.finish().to_be_bytes());
+    }
+
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .fold(String::with_capacity(32), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        });
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, error::Error, io::Read};
+
+    use image::RgbImage;
+    use quick_xml::events::Event;
+    use zip::ZipArchive;
+
+    use super::*;
+
+    type Attributes = HashMap<String, String>;
+
+    fn image(format: ImageFormat) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut buf = Vec::new();
+        RgbImage::new(2, 3).write_to(&mut Cursor::new(&mut buf), format)?;
+
+        Ok(buf)
+    }
+
+    /// The elements of the document with their attributes, failing if it isn't well-formed.
+    fn elements(xml: &str) -> Result<Vec<(String, Attributes)>, Box<dyn Error>> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let mut elements = Vec::new();
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(element) | Event::Empty(element) => {
+                    let mut attributes = HashMap::new();
+                    for attribute in element.attributes() {
+                        let attribute = attribute?;
+                        attributes.insert(
+                            attribute.key.into_inner().to_string(),
+                            attribute.value.to_string(),
+                        );
+                    }
+                    elements.push((element.name().into_inner().to_string(), attributes));
+                }
+                Event::Eof => return Ok(elements),
+                _ => {}
+            }
+        }
+    }
+
+    /// The attributes of the elements of this name.
+    fn attributes<'a>(
+        elements: &'a [(String, Attributes)],
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a Attributes> {
+        elements
+            .iter()
+            .filter(move |(element, _)| element == name)
+            .map(|(_, attributes)| attributes)
+    }
+
+    fn read_entry(
+        archive: &mut ZipArchive<Cursor<Vec<u8>>>,
+        name: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut content = String::new();
+        archive.by_name(name)?.read_to_string(&mut content)?;
+
+        Ok(content)
+    }
+
+    #[test]
+    fn test_package() -> Result<(), Box<dyn Error>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut package = Package::new(Some("Tom & Jerry".to_string()));
+        package.insert_page(&mut writer, &image(ImageFormat::Png)?)?;
+        package.insert_page(&mut writer, &image(ImageFormat::Bmp)?)?;
+        package.finish(&mut writer)?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+
+        // The mimetype comes first, stored
+        let mut mimetype = archive.by_index(0)?;
+        let mut content = String::new();
+        mimetype.read_to_string(&mut content)?;
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        assert_eq!(content, MIMETYPE);
+        drop(mimetype);
+
+        let opf = elements(&read_entry(&mut archive, PACKAGE_PATH)?)?;
+        let items = attributes(&opf, "item")
+            .map(|item| (item["id"].as_str(), item))
+            .collect::<HashMap<_, _>>();
+        for item in items.values() {
+            let path = format!("OEBPS/{}", item["href"]);
+            assert!(archive.index_for_name(&path).is_some(), "{path} is missing");
+            let media_type = item["media-type"].as_str();
+            assert!(
+                CORE_MEDIA_TYPES.contains(&media_type) || media_type == "application/xhtml+xml"
+            );
+        }
+        assert_eq!(items["nav"]["properties"], "nav");
+        // The BMP page is converted
+        assert_eq!(items["image-00001"]["href"], "images/00001.png");
+
+        let spine = attributes(&opf, "itemref")
+            .map(|itemref| itemref["idref"].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(spine, ["page-00000", "page-00001"]);
+
+        let nav = elements(&read_entry(&mut archive, NAV_PATH)?)?;
+        let links = attributes(&nav, "a")
+            .map(|link| link["href"].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            [
+                "pages/00000.xhtml",
+                "pages/00000.xhtml",
+                "pages/00001.xhtml"
+            ]
+        );
+
+        elements(&read_entry(&mut archive, "OEBPS/pages/00001.xhtml")?)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_media_type() -> Result<(), Box<dyn Error>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut package = Package::new(None);
+        let avif = image(ImageFormat::Avif)?;
+
+        assert!(matches!(
+            package.insert_page_with_dimensions(&mut writer, &avif, 2, 3),
+            Err(InsertionError::EpubMediaType("image/avif"))
+        ));
+        assert!(package.is_empty());
+
+        Ok(())
+    }
+}
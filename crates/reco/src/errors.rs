@@ -1,4 +1,4 @@
-use std::io;
+use std::{io, path::PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,6 +8,15 @@ pub enum CreateArchiveError {
 
     #[error("archive creation error: {0}")]
     ArchiveCreation(io::Error),
+
+    #[error("archive append error: {0}")]
+    Append(zip::result::ZipError),
+
+    #[error("epub archives can't be appended to")]
+    EpubAppend,
+
+    #[error("{} already exists", .0.display())]
+    AlreadyExists(PathBuf),
 }
 
 #[derive(Debug, Error)]
@@ -30,27 +39,84 @@ pub enum InsertionError {
     #[error("image encode error {0}")]
     ImageEncode(image::error::ImageError),
 
+    #[error("image decode error {0}")]
+    ImageDecode(image::error::ImageError),
+
     #[error("file is not an image")]
     InvalidFormat,
+
+    #[error("archive entry read error: {0}")]
+    EntryRead(BackendError),
+
+    #[error("invalid sidecar entry name: {0}")]
+    SidecarName(String),
+
+    #[error("epub archives can't have sidecar entries")]
+    EpubSidecar,
+
+    #[error("{0} images can't be written to epub archives")]
+    EpubMediaType(&'static str),
 }
 
 #[derive(Debug, Error)]
-#[error(transparent)]
-pub struct ArchiveFinishError(#[from] zip::result::ZipError);
+#[error("thread pool creation error: {0}")]
+pub struct PipelineCreationError(#[from] rayon::ThreadPoolBuildError);
 
 #[derive(Debug, Error)]
-pub enum SpineCreationError {
-    #[error("zip access by name failure: {0}")]
-    ZipByName(zip::result::ZipError),
+pub enum ArchiveFinishError {
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error(transparent)]
+    Persist(#[from] PersistError),
 
-    #[error("zip file read error: {0}")]
-    ZipFileRead(io::Error),
+    #[error("sidecar insertion error: {0}")]
+    Sidecar(InsertionError),
+}
+
+#[derive(Debug, Error)]
+pub enum PersistError {
+    #[error("output sync error: {0}")]
+    Sync(io::Error),
+
+    #[error("output rename error: {0}")]
+    Rename(io::Error),
+
+    #[error("{} already exists", .0.display())]
+    AlreadyExists(PathBuf),
+}
+
+#[derive(Debug, Error)]
+pub enum BackendError {
+    #[error("zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("rar error: {0}")]
+    Rar(#[from] unrar::error::UnrarError),
+
+    #[error("7z error: {0}")]
+    SevenZ(#[from] sevenz_rust2::Error),
+
+    #[error("archive read error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("archive entry not found: {0}")]
+    EntryNotFound(String),
+
+    #[error("rar archives can only be read from a path")]
+    UnsupportedReader,
+}
+
+#[derive(Debug, Error)]
+pub enum SpineCreationError {
+    #[error("archive entries read error: {0}")]
+    EntriesRead(#[from] BackendError),
 }
 
 #[derive(Debug, Error)]
 pub enum ReaderCreationError {
     #[error("archive creation error: {0}")]
-    ArchiveCreation(zip::result::ZipError),
+    ArchiveCreation(BackendError),
 
     #[error("spine creation error: {0}")]
     SpineCreation(#[from] SpineCreationError),
@@ -70,11 +136,8 @@ pub enum LoadImageError {
     #[error("zip access by index failure: {0} not found")]
     ZipByIndex(usize),
 
-    #[error("zip access by name failure: {0}")]
-    ZipByName(zip::result::ZipError),
-
-    #[error("zip file read error: {0}")]
-    ZipFileRead(io::Error),
+    #[error("archive entry read error: {0}")]
+    EntryRead(BackendError),
 
     #[error("image guessed format error: {0}")]
     ImageGuessedFormat(io::Error),
@@ -82,3 +145,35 @@ pub enum LoadImageError {
     #[error("image decode error: {0}")]
     ImageDecode(image::error::ImageError),
 }
+
+#[derive(Debug, Error)]
+pub enum ComicInfoParseError {
+    #[error("xml error: {0}")]
+    Xml(#[from] quick_xml::Error),
+
+    #[error("missing ComicInfo root element")]
+    MissingRoot,
+}
+
+#[derive(Debug, Error)]
+#[error("invalid page selection: {0}, expected e.g. 1-10,25 or 30-")]
+pub struct PageSelectionParseError(pub(crate) String);
+
+#[derive(Debug, Error)]
+#[error(
+    "invalid naming: {0}, expected index, index:<width>, original or a template with {{page}} or {{name}}"
+)]
+pub struct NamingParseError(pub(crate) String);
+
+#[derive(Debug, Error)]
+#[error("unknown file name encoding: {0}, expected auto, cp437 or e.g. shift_jis or gbk")]
+pub struct NameEncodingParseError(pub(crate) String);
+
+#[derive(Debug, Error)]
+pub enum LoadComicInfoError {
+    #[error("archive entry read error: {0}")]
+    EntryRead(BackendError),
+
+    #[error("comic info parse error: {0}")]
+    Parse(#[from] ComicInfoParseError),
+}
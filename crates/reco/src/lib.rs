@@ -1,13 +1,33 @@
 #![deny(clippy::all, clippy::pedantic, clippy::unwrap_used)]
 #![expect(clippy::missing_errors_doc)]
 
+pub use comic_info::ComicInfo;
 pub use errors::{
-    ArchiveFinishError, CreateArchiveError, InsertionError, LoadImageError, ReaderCreationError,
+    ArchiveFinishError, BackendError, ComicInfoParseError, CreateArchiveError, InsertionError,
+    LoadComicInfoError, LoadImageError, NameEncodingParseError, NamingParseError,
+    PageSelectionParseError, PersistError, PipelineCreationError, ReaderCreationError,
     ReaderOpenError,
 };
-pub use reader::Reader;
+pub use naming::Naming;
+pub use output::TempOutput;
+pub use pages::PageSelection;
+pub use pipeline::Pipeline;
+pub use progress::{NoProgress, Progress};
+pub use reader::{Reader, SpineOrder};
+pub use report::{ErrorPolicy, Report};
 pub use writer::Writer;
 
+pub mod backend;
+pub mod comic_info;
+pub mod encoding;
+pub mod epub;
 pub mod errors;
+pub mod naming;
+pub mod output;
+pub mod pages;
+pub mod pipeline;
+pub mod progress;
 pub mod reader;
+pub mod report;
+pub mod toc;
 pub mod writer;
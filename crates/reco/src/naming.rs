@@ -0,0 +1,219 @@
+//! How the pages inserted by a [`Writer`](crate::Writer) are named.
+
+use std::{fmt::Write, str::FromStr};
+
+use crate::errors::NamingParseError;
+
+/// The width of the default zero-padded indexes.
+const DEFAULT_WIDTH: usize = 20;
+
+/// The width of the zero-padded source indexes prefixing the [`Naming::Original`] names.
+const SOURCE_WIDTH: usize = 3;
+
+/// The entry name of the inserted pages (CBZ only), without the extension (normalised, e.g.
+/// `.JPG` to `.jpg`, and added by the writer). Names are sanitised to be valid on every platform,
+/// and made unique by adding ` (2)`, ` (3)`... to the duplicates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Naming {
+    /// The page index (starting at 0) zero-padded to this width, e.g. `0003.jpg`.
+    Index(usize),
+    /// The source file name of the page (e.g. the packed image), the page index when it has none.
+    /// The folders of the pages copied from archives are kept, and their names are prefixed with
+    /// the index of their archive when merging (e.g. `002 - Chapter 1/001.jpg`), so the pages of
+    /// each archive stay together.
+    Original,
+    /// A name built from the page number, the chapter number and the source file name.
+    Template(Template),
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Self::Index(DEFAULT_WIDTH)
+    }
+}
+
+impl Naming {
+    /// The name (without extension) of the page at this index, `chapter` being 0 until the first
+    /// chapter starts.
+    pub(crate) fn name(&self, index: usize, chapter: usize, source: Option<&str>) -> String {
+        let name = match (self, source) {
+            (Self::Original, Some(source)) if chapter > 0 => {
+                sanitize_path(&format!("{chapter:0>SOURCE_WIDTH$} - {source}"))
+            }
+            (Self::Original, Some(source)) => sanitize_path(source),
+            (Self::Template(template), _) => template.render(index + 1, chapter.max(1), source),
+            _ => String::new(),
+        };
+
+        if name.is_empty() {
+            let width = match self {
+                Self::Index(width) => *width,
+                _ => DEFAULT_WIDTH,
+            };
+            return format!("{index:0>width$}");
+        }
+
+        name
+    }
+}
+
+/// Parses `index` (or `index:<width>`), `original` or a template.
+impl FromStr for Naming {
+    type Err = NamingParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "index" => Ok(Self::default()),
+            "original" => Ok(Self::Original),
+            _ => match s.strip_prefix("index:") {
+                Some(width) => width
+                    .parse()
+                    .map(Self::Index)
+                    .map_err(|_| NamingParseError(s.to_string())),
+                None => s.parse().map(Self::Template),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    /// The page number (starting at 1), zero-padded to the width.
+    Page(usize),
+    /// The chapter number (starting at 1), zero-padded to the width.
+    Chapter(usize),
+    /// The source file name.
+    Name,
+}
+
+/// A name template, e.g. `Chapter {chapter}/{page:3}` (which also creates a folder per chapter),
+/// with `{page}`, `{chapter}` and `{name}` placeholders, the numbers being zero-padded to the
+/// optional width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Part>);
+
+impl Template {
+    fn render(&self, page: usize, chapter: usize, source: Option<&str>) -> String {
+        let mut name = String::new();
+        for part in &self.0 {
+            let _ = match part {
+                Part::Text(text) => write!(name, "{text}"),
+                Part::Page(width) => write!(name, "{page:0>width$}"),
+                Part::Chapter(width) => write!(name, "{chapter:0>width$}"),
+                Part::Name => write!(name, "{}", sanitize(source.unwrap_or_default())),
+            };
+        }
+
+        sanitize_path(&name)
+    }
+}
+
+impl FromStr for Template {
+    type Err = NamingParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || NamingParseError(s.to_string());
+        let mut parts = Vec::new();
+        let mut rest = s;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}').ok_or_else(err)? + start;
+            let (placeholder, width) = match rest[start + 1..end].split_once(':') {
+                Some((placeholder, width)) => (placeholder, width.parse().map_err(|_| err())?),
+                None => (&rest[start + 1..end], 0),
+            };
+            parts.push(match placeholder {
+                "page" => Part::Page(width),
+                "chapter" => Part::Chapter(width),
+                "name" if width == 0 => Part::Name,
+                _ => return Err(err()),
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        // Pages would all have the same name otherwise
+        if !parts
+            .iter()
+            .any(|part| matches!(part, Part::Page(_) | Part::Name))
+        {
+            return Err(err());
+        }
+
+        Ok(Self(parts))
+    }
+}
+
+/// Replaces the characters which aren't valid in file names on some platforms (e.g. `:` or `/`),
+/// and trims the spaces and dots (e.g. of `..`).
+pub(crate) fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_string()
+}
+
+/// Sanitises each folder of a `/` separated path, dropping the empty ones (e.g. `..`).
+fn sanitize_path(path: &str) -> String {
+    path.split('/')
+        .map(sanitize)
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The lowercase extension, with its dot, e.g. `.jpg` from `JPG` or `.JPG`.
+pub(crate) fn normalize_ext(ext: &str) -> String {
+    let ext = ext.trim().trim_start_matches('.');
+    if ext.is_empty() {
+        return String::new();
+    }
+
+    format!(".{}", ext.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_naming() -> Result<(), NamingParseError> {
+        assert_eq!(
+            Naming::default().name(3, 1, Some("cover")),
+            "0".repeat(19) + "3"
+        );
+        assert_eq!("index:3".parse::<Naming>()?.name(3, 1, None), "003");
+        assert_eq!(Naming::Original.name(3, 0, Some("a: b?")), "a_ b_");
+        assert_eq!(Naming::Original.name(3, 0, Some("../ch 1/001")), "ch 1/001");
+        assert_eq!(
+            Naming::Original.name(3, 2, Some("ch 1/001")),
+            "002 - ch 1/001"
+        );
+        assert_eq!(Naming::Original.name(3, 1, None), "0".repeat(19) + "3");
+
+        let naming = "Chapter {chapter:2}/{page:3} - {name}".parse::<Naming>()?;
+        assert_eq!(
+            naming.name(3, 1, Some("../cover")),
+            "Chapter 01/004 - _cover"
+        );
+        assert!("{chapter}".parse::<Naming>().is_err());
+        assert!("{page".parse::<Naming>().is_err());
+        assert!("{title}".parse::<Naming>().is_err());
+
+        assert_eq!(normalize_ext("JPG"), ".jpg");
+        assert_eq!(normalize_ext(".png"), ".png");
+
+        Ok(())
+    }
+}
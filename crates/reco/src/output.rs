@@ -0,0 +1,92 @@
+//! Files written next to their destination, and moved there once complete.
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use tempfile::{Builder, TempPath};
+
+use crate::errors::PersistError;
+
+/// A temporary file with a unique name, next to the output path. It is synced then renamed to the
+/// output path once persisted, and removed otherwise (e.g. on error), so an existing file is
+/// never left half written, even by a crash.
+#[derive(Debug)]
+pub struct TempOutput {
+    /// A handle on the temporary file, to sync it whoever writes it.
+    file: File,
+    tmp_path: TempPath,
+    path: PathBuf,
+    no_clobber: bool,
+}
+
+impl TempOutput {
+    /// Creates the temporary file of the output path, returned opened for reading and writing.
+    /// With `no_clobber`, persisting fails if the output path exists by then.
+    pub fn create(path: &Path, no_clobber: bool) -> io::Result<(File, Self)> {
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        // Temporary files are private by default, the output gets the permissions of the file it
+        // replaces, or those of a newly created file
+        let prefix = format!(".{name}.");
+        let mut builder = Builder::new();
+        builder.prefix(&prefix).suffix(".tmp");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            builder.permissions(fs::Permissions::from_mode(0o666));
+        }
+        let (file, tmp_path) = builder.tempfile_in(dir)?.into_parts();
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        let output = Self {
+            file: file.try_clone()?,
+            tmp_path,
+            path: path.to_path_buf(),
+            no_clobber,
+        };
+
+        Ok((file, output))
+    }
+
+    /// The path of the temporary file.
+    #[must_use]
+    pub fn tmp_path(&self) -> &Path {
+        &self.tmp_path
+    }
+
+    /// Persists the temporary file to another path than the one it was created for, in the same
+    /// directory (e.g. once a name depending on other outputs is known).
+    pub fn persist_as(mut self, path: &Path) -> Result<(), PersistError> {
+        self.path = path.to_path_buf();
+        self.persist()
+    }
+
+    /// Moves the temporary file to the output path once synced, atomically (and without
+    /// replacing an existing file with `no_clobber`).
+    pub fn persist(self) -> Result<(), PersistError> {
+        self.file.sync_all().map_err(PersistError::Sync)?;
+        drop(self.file);
+
+        let res = if self.no_clobber {
+            self.tmp_path.persist_noclobber(&self.path)
+        } else {
+            self.tmp_path.persist(&self.path)
+        };
+
+        res.map_err(|err| {
+            if self.no_clobber && err.error.kind() == io::ErrorKind::AlreadyExists {
+                PersistError::AlreadyExists(self.path)
+            } else {
+                PersistError::Rename(err.error)
+            }
+        })
+    }
+}
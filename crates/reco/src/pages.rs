@@ -0,0 +1,73 @@
+//! Selection of pages by number, as given on the command line.
+
+use std::{ops::RangeInclusive, str::FromStr};
+
+use crate::errors::PageSelectionParseError;
+
+/// Page numbers (starting at 1) ranges, e.g. `1-10,25` or `30-` (up to the last page).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSelection(Vec<RangeInclusive<usize>>);
+
+impl PageSelection {
+    #[must_use]
+    pub fn contains(&self, page: usize) -> bool {
+        self.0.iter().any(|range| range.contains(&page))
+    }
+
+    /// The selected page numbers of an archive of `count` pages, in ascending order.
+    #[must_use]
+    pub fn pages(&self, count: usize) -> Vec<usize> {
+        (1..=count).filter(|&page| self.contains(page)).collect()
+    }
+}
+
+impl FromStr for PageSelection {
+    type Err = PageSelectionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || PageSelectionParseError(s.to_string());
+        let page = |page: &str| match page.trim().parse::<usize>() {
+            Ok(page) if page > 0 => Ok(page),
+            _ => Err(err()),
+        };
+
+        s.split(',')
+            .map(str::trim)
+            .map(|range| match range.split_once('-') {
+                Some((start, "")) => Ok(page(start)?..=usize::MAX),
+                Some((start, end)) => {
+                    let (start, end) = (page(start)?, page(end)?);
+                    if start > end {
+                        return Err(err());
+                    }
+                    Ok(start..=end)
+                }
+                None => page(range).map(|page| page..=page),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_selection() -> Result<(), PageSelectionParseError> {
+        let pages = "1-3, 7,10-".parse::<PageSelection>()?;
+
+        assert!(
+            [1, 2, 3, 7, 10, 1000]
+                .into_iter()
+                .all(|page| pages.contains(page))
+        );
+        assert!(![4, 6, 8, 9].into_iter().any(|page| pages.contains(page)));
+        assert_eq!(pages.pages(8), [1, 2, 3, 7]);
+        assert!("0-3".parse::<PageSelection>().is_err());
+        assert!("3-1".parse::<PageSelection>().is_err());
+        assert!("1,,2".parse::<PageSelection>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Parallel processing of pages (decoding, transforming, encoding), consumed in order by a single
+//! thread (e.g. the one writing the archive).
+
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc,
+};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::errors::PipelineCreationError;
+
+/// Items in flight (waiting, being processed, or processed but not consumed yet) per thread,
+/// bounding the memory used by the pipeline.
+static IN_FLIGHT_PER_THREAD: usize = 2;
+
+pub struct Pipeline {
+    pool: ThreadPool,
+}
+
+impl Pipeline {
+    /// A pipeline processing items on `jobs` threads, one per core if 0.
+    pub fn new(jobs: usize) -> Result<Self, PipelineCreationError> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .thread_name(|index| format!("reco-pipeline-{index}"))
+            .build()?;
+
+        Ok(Self { pool })
+    }
+
+    #[must_use]
+    pub fn jobs(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Processes the items on the pipeline threads, then consumes their results in the items
+    /// order on the calling thread. Stops at the first consumption error.
+    ///
+    /// Items are pulled from the iterator on the calling thread, so they should be cheap handles
+    /// (e.g. encoded data), the expensive work (e.g. decoding) being done by `process`. At most
+    /// `jobs` items are processed at once, and at most `2 * jobs` items (and their results) are in
+    /// flight, e.g. with pages decoded by `process`, at most `jobs` decoded pages are in memory.
+    pub fn run<T, U, E>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        process: impl Fn(T) -> U + Sync,
+        mut consume: impl FnMut(U) -> Result<(), E>,
+    ) -> Result<(), E>
+    where
+        T: Send,
+        U: Send,
+    {
+        let window = self.jobs() * IN_FLIGHT_PER_THREAD;
+        let mut items = items.into_iter().enumerate();
+        let (sender, receiver) = mpsc::channel();
+        let process = &process;
+
+        self.pool.in_place_scope(|scope| {
+            // Results processed out of order, waiting for the previous ones
+            let mut processed = BTreeMap::new();
+            let (mut started, mut consumed) = (0, 0);
+
+            loop {
+                while started - consumed < window
+                    && let Some((index, item)) = items.next()
+                {
+                    let sender = sender.clone();
+                    scope.spawn(move |_| {
+                        // Panics are resumed on the calling thread, which would wait forever
+                        // for the result otherwise
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| process(item)));
+                        let _ = sender.send((index, result));
+                    });
+                    started += 1;
+                }
+
+                if consumed == started {
+                    return Ok(());
+                }
+
+                let result = loop {
+                    if let Some(result) = processed.remove(&consumed) {
+                        break result;
+                    }
+                    // The sender kept by this thread keeps the channel open
+                    if let Ok((index, result)) = receiver.recv() {
+                        processed.insert(index, result);
+                    }
+                };
+                consumed += 1;
+
+                consume(result.unwrap_or_else(|payload| panic::resume_unwind(payload)))?;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn test_run() -> Result<(), PipelineCreationError> {
+        let pipeline = Pipeline::new(3)?;
+        let mut consumed = Vec::new();
+
+        // Later items are processed faster, their results wait for the earlier ones
+        let res = pipeline.run(
+            0..20,
+            |item| {
+                thread::sleep(Duration::from_millis(20 - item));
+                item * 2
+            },
+            |result| {
+                consumed.push(result);
+                Ok::<_, ()>(())
+            },
+        );
+        assert_eq!(res, Ok(()));
+        assert_eq!(consumed, (0..20).map(|item| item * 2).collect::<Vec<_>>());
+
+        let res = pipeline.run(
+            0..20,
+            |item| item,
+            |item| if item < 5 { Ok(()) } else { Err(item) },
+        );
+        assert_eq!(res, Err(5));
+
+        Ok(())
+    }
+}
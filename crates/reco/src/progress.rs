@@ -0,0 +1,23 @@
+//! Progress reporting of long running jobs (e.g. packing or merging archives).
+
+use std::path::Path;
+
+/// Observes the progress of a job, called from the thread running it. All the methods do nothing
+/// by default.
+pub trait Progress {
+    /// More pages are to be processed, e.g. once the pages of an archive are listed. Pages are
+    /// source images for jobs reading images.
+    fn add_total(&self, _pages: usize) {}
+
+    /// A file (e.g. an image or an archive) starts being processed.
+    fn start_file(&self, _path: &Path) {}
+
+    /// A page was processed, `bytes` being written for it.
+    fn page_done(&self, _bytes: u64) {}
+}
+
+/// Ignores the progress.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
@@ -1,16 +1,66 @@
 use std::{
-    fs::File,
-    io::{self, Cursor, Read, Seek},
+    collections::HashSet,
+    io::{Cursor, Read, Seek},
     path::Path,
 };
 
 use image::DynamicImage;
 use image::ImageReader;
 use infer::is_image;
-use tracing::debug;
-use zip::{ZipArchive, read::ZipFile, result::ZipResult};
+use lexical_sort::{StringSort, natural_lexical_cmp};
+use tracing::{debug, warn};
+use zip::read::ZipFile;
+
+use super::{
+    backend::{self, Backend, BoxedReader, NameEncoding},
+    comic_info::{COMIC_INFO_PATH, ComicInfo, PageType},
+    errors::{
+        BackendError, LoadComicInfoError, LoadImageError, ReaderCreationError, ReaderOpenError,
+        SpineCreationError,
+    },
+    toc::{self, Chapter},
+};
+
+/// How the pages of an archive are ordered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpineOrder {
+    /// By file name, numbers being compared by value (`page2.jpg` comes before `page10.jpg`).
+    #[default]
+    Natural,
+    /// By file name, character by character (`page10.jpg` comes before `page2.jpg`).
+    Lexical,
+    /// As stored in the zip central directory.
+    Archive,
+    /// As listed by the `ComicInfo.xml` pages (deleted pages are skipped), the images it doesn't
+    /// list coming after, in natural order. Falls back to the natural order without `ComicInfo`.
+    ComicInfo,
+}
 
-use super::errors::{LoadImageError, ReaderCreationError, ReaderOpenError, SpineCreationError};
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    order: SpineOrder,
+    name_encoding: NameEncoding,
+}
+
+impl Options {
+    #[must_use]
+    pub fn with_order(mut self, order: SpineOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// The encoding of the zip entry names not flagged as UTF-8, detected by default.
+    #[must_use]
+    pub fn with_name_encoding(mut self, name_encoding: NameEncoding) -> Self {
+        self.name_encoding = name_encoding;
+        self
+    }
+
+    #[must_use]
+    pub fn order(&self) -> SpineOrder {
+        self.order
+    }
+}
 
 #[derive(Debug)]
 pub struct Spine {
@@ -19,39 +69,47 @@ pub struct Spine {
 }
 
 impl Spine {
-    pub fn try_new<R: Read + Seek>(
-        archive: &mut ZipArchive<R>,
-    ) -> Result<Self, SpineCreationError> {
-        let file_names = archive
-            .file_names()
-            .map(ToString::to_string)
-            .collect::<Vec<_>>();
-
-        let mut spine = Vec::with_capacity(file_names.len());
+    pub fn try_new(archive: &mut dyn Backend) -> Result<Self, SpineCreationError> {
+        Self::try_new_with_order(archive, SpineOrder::default())
+    }
 
-        for file_name in file_names {
-            let mut file = archive
-                .by_name(&file_name)
-                .map_err(SpineCreationError::ZipByName)?;
+    pub fn try_new_with_order(
+        archive: &mut dyn Backend,
+        order: SpineOrder,
+    ) -> Result<Self, SpineCreationError> {
+        let mut images = HashSet::new();
 
-            if !file.is_file() {
-                continue;
+        for (file_name, prefix) in archive.read_prefixes(32)? {
+            if is_image(&prefix) {
+                images.insert(file_name);
+            } else {
+                debug!("{file_name} is not an image, skipping");
             }
+        }
 
-            let mut buf = [0; 32];
-            file.read_exact(&mut buf)
-                .map_err(SpineCreationError::ZipFileRead)?;
+        // Prefixes are not necessarily read in archive order
+        let mut spine = archive
+            .file_names()
+            .iter()
+            .filter(|file_name| images.contains(file_name.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
 
-            if !is_image(&buf) {
-                debug!("{file_name} is not an image, skipping");
-                continue;
+        match order {
+            SpineOrder::Natural => spine.string_sort_unstable(natural_lexical_cmp),
+            SpineOrder::Lexical => spine.sort_unstable(),
+            SpineOrder::Archive => {}
+            SpineOrder::ComicInfo => {
+                spine.string_sort_unstable(natural_lexical_cmp);
+
+                match load_comic_info(archive) {
+                    Ok(Some(comic_info)) => spine = comic_info_order(spine, &comic_info),
+                    Ok(None) => {}
+                    Err(err) => warn!("comic info load error, using the natural order: {err}"),
+                }
             }
-
-            spine.push(file_name);
         }
 
-        spine.sort_unstable();
-
         Ok(Self {
             spine,
             current_index: 0,
@@ -85,8 +143,9 @@ impl Spine {
         self.set_current_index(self.current_index + 1);
     }
 
+    /// Sets the current index, clamped to the last page (0 for an empty spine).
     pub fn set_current_index(&mut self, index: usize) {
-        self.current_index = index.clamp(0, self.len() - 1);
+        self.current_index = index.min(self.len().saturating_sub(1));
     }
 
     #[must_use]
@@ -99,53 +158,140 @@ impl Spine {
     }
 }
 
-pub struct Reader<R> {
+pub struct Reader {
     spine: Spine,
-    archive: ZipArchive<R>,
+    chapters: Vec<Chapter>,
+    archive: Box<dyn Backend>,
 }
 
-impl<R> Reader<R> {
+impl Reader {
+    #[must_use]
     pub fn spine(&self) -> &Spine {
         &self.spine
     }
 
+    /// The table of contents, from the `ComicInfo.xml` bookmarks if any, the folders otherwise.
+    /// Empty if the archive has neither.
+    #[must_use]
+    pub fn chapters(&self) -> &[Chapter] {
+        &self.chapters
+    }
+
+    /// The innermost chapter containing the current page.
+    #[must_use]
+    pub fn current_chapter(&self) -> Option<&Chapter> {
+        let current_index = self.spine.current_index;
+
+        toc::flatten(&self.chapters)
+            .into_iter()
+            .filter(|(_, chapter)| chapter.index <= current_index)
+            .max_by_key(|(depth, chapter)| (chapter.index, *depth))
+            .map(|(_, chapter)| chapter)
+    }
+
+    /// Goes to the first page of the next chapter, if any.
+    pub fn go_to_next_chapter(&mut self) {
+        let current_index = self.spine.current_index;
+
+        if let Some(index) = toc::starts(&self.chapters)
+            .into_iter()
+            .find(|index| *index > current_index)
+        {
+            self.spine.set_current_index(index);
+        }
+    }
+
+    /// Goes to the first page of the current chapter, or of the previous one when already there.
+    pub fn go_to_prev_chapter(&mut self) {
+        let current_index = self.spine.current_index;
+
+        if let Some(index) = toc::starts(&self.chapters)
+            .into_iter()
+            .rfind(|index| *index < current_index)
+        {
+            self.spine.set_current_index(index);
+        }
+    }
+
     pub fn spine_mut(&mut self) -> &mut Spine {
         &mut self.spine
     }
 }
 
-impl<R> Reader<R>
-where
-    R: Read + Seek,
-{
-    pub fn try_new(reader: R) -> Result<Self, ReaderCreationError> {
-        let mut archive = ZipArchive::new(reader).map_err(ReaderCreationError::ArchiveCreation)?;
-        let spine = Spine::try_new(&mut archive)?;
+impl Reader {
+    /// Reads a zip or 7z archive, use [`Reader::try_open`] for rar archives.
+    pub fn try_new<R>(reader: R) -> Result<Self, ReaderCreationError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        Self::try_new_with_options(reader, Options::default())
+    }
 
-        Ok(Self { spine, archive })
+    pub fn try_new_with_options<R>(reader: R, opts: Options) -> Result<Self, ReaderCreationError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let archive = backend::from_reader_with_name_encoding(reader, opts.name_encoding)
+            .map_err(ReaderCreationError::ArchiveCreation)?;
+        Self::try_from_backend(archive, opts)
     }
 
-    pub fn by_name(&mut self, file_name: &str) -> ZipResult<ZipFile<'_, R>> {
-        self.archive.by_name(file_name)
+    pub fn try_from_backend(
+        mut archive: Box<dyn Backend>,
+        opts: Options,
+    ) -> Result<Self, ReaderCreationError> {
+        let spine = Spine::try_new_with_order(archive.as_mut(), opts.order)?;
+
+        let comic_info = load_comic_info(archive.as_mut()).unwrap_or_else(|err| {
+            warn!("comic info load error, ignoring its bookmarks: {err}");
+            None
+        });
+        let chapters = comic_info
+            .map(|comic_info| toc::from_bookmarks(&spine.spine, &comic_info))
+            .filter(|chapters| !chapters.is_empty())
+            .unwrap_or_else(|| toc::from_folders(&spine.spine));
+
+        Ok(Self {
+            spine,
+            chapters,
+            archive,
+        })
+    }
+
+    /// The names of all the archive files, whether they are pages or not.
+    #[must_use]
+    pub fn file_names(&self) -> &[String] {
+        self.archive.file_names()
+    }
+
+    /// Reads an entry of the archive entirely.
+    pub fn read(&mut self, file_name: &str) -> Result<Vec<u8>, BackendError> {
+        self.archive.read(file_name)
+    }
+
+    /// Reads (at most) the first `len` bytes of an entry, e.g. an image header.
+    pub fn read_prefix(&mut self, file_name: &str, len: usize) -> Result<Vec<u8>, BackendError> {
+        self.archive.read_prefix(file_name, len)
+    }
+
+    /// The entry as stored in a zip archive (still compressed), `None` for other formats.
+    pub fn zip_entry(
+        &mut self,
+        file_name: &str,
+    ) -> Result<Option<ZipFile<'_, BoxedReader>>, BackendError> {
+        self.archive.zip_entry(file_name)
     }
 
-    #[expect(clippy::cast_possible_truncation)]
     pub fn load_current_img(&mut self) -> Result<DynamicImage, LoadImageError> {
         let Some(file_name) = self.spine.get_current_file_name() else {
             return Err(LoadImageError::ZipByIndex(self.spine.current_index));
         };
-        let mut file = self
+        let buf = self
             .archive
-            .by_name(file_name)
-            .map_err(LoadImageError::ZipByName)?;
+            .read(file_name)
+            .map_err(LoadImageError::EntryRead)?;
 
-        let mut buf = Cursor::new(Vec::with_capacity(file.size() as usize));
-        io::copy(&mut file, &mut buf).map_err(LoadImageError::ZipFileRead)?;
-        let _ = buf
-            .seek(io::SeekFrom::Start(0))
-            .map_err(LoadImageError::ZipFileRead)?;
-
-        let reader = ImageReader::new(buf)
+        let reader = ImageReader::new(Cursor::new(buf))
             .with_guessed_format()
             .map_err(LoadImageError::ImageGuessedFormat)?;
         let img = reader.decode().map_err(LoadImageError::ImageDecode)?;
@@ -153,6 +299,17 @@ where
         Ok(img)
     }
 
+    /// The archive comment (zip and rar only), often left empty.
+    #[must_use]
+    pub fn comment(&self) -> &[u8] {
+        self.archive.comment()
+    }
+
+    /// Parses the archive `ComicInfo.xml` (looked up case-insensitively), if any.
+    pub fn comic_info(&mut self) -> Result<Option<ComicInfo>, LoadComicInfoError> {
+        load_comic_info(self.archive.as_mut())
+    }
+
     pub fn go_to_prev_index(&mut self) {
         self.spine.go_to_prev_index();
     }
@@ -166,21 +323,69 @@ where
     }
 }
 
-impl Reader<File> {
+impl Reader {
+    /// Opens a zip, rar or 7z archive, its format being guessed from its content.
     pub fn try_open(path: &Path) -> Result<Self, ReaderOpenError> {
-        let file = File::open(path).map_err(ReaderOpenError::FileOpen)?;
-        Ok(Self::try_new(file)?)
+        Self::try_open_with_options(path, Options::default())
     }
+
+    pub fn try_open_with_options(path: &Path, opts: Options) -> Result<Self, ReaderOpenError> {
+        let archive = backend::open_with_name_encoding(path, opts.name_encoding).map_err(
+            |err| match err {
+                BackendError::Io(err) => ReaderOpenError::FileOpen(err),
+                err => ReaderOpenError::ReaderCreation(ReaderCreationError::ArchiveCreation(err)),
+            },
+        )?;
+
+        Ok(Self::try_from_backend(archive, opts)?)
+    }
+}
+
+fn load_comic_info(archive: &mut dyn Backend) -> Result<Option<ComicInfo>, LoadComicInfoError> {
+    let Some(file_name) = archive
+        .file_names()
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(COMIC_INFO_PATH))
+        .cloned()
+    else {
+        return Ok(None);
+    };
+
+    let buf = archive
+        .read(&file_name)
+        .map_err(LoadComicInfoError::EntryRead)?;
+
+    Ok(Some(ComicInfo::from_xml(&String::from_utf8_lossy(&buf))?))
+}
+
+/// Reorders the (naturally sorted) spine as listed by the `ComicInfo` pages, whose `image` is the
+/// index in the naturally sorted spine.
+fn comic_info_order(spine: Vec<String>, comic_info: &ComicInfo) -> Vec<String> {
+    let mut images = spine.into_iter().map(Some).collect::<Vec<_>>();
+    let mut ordered = Vec::with_capacity(images.len());
+
+    for page in &comic_info.pages {
+        let Some(image) = images.get_mut(page.image).and_then(Option::take) else {
+            continue;
+        };
+
+        if page.kind != Some(PageType::Deleted) {
+            ordered.push(image);
+        }
+    }
+
+    ordered.extend(images.into_iter().flatten());
+    ordered
 }
 
-impl<R> Reader<R> {
+impl Reader {
     #[must_use]
     pub fn iter(&self) -> IndicesIter<'_> {
         IndicesIter::new(&self.spine.spine)
     }
 }
 
-impl<'a, R> IntoIterator for &'a Reader<R> {
+impl<'a> IntoIterator for &'a Reader {
     type IntoIter = IndicesIter<'a>;
     type Item = &'a str;
 
@@ -215,3 +420,32 @@ impl<'a> Iterator for IndicesIter<'a> {
         Some(item)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(len: usize) -> Spine {
+        Spine {
+            spine: (1..=len).map(|page| format!("{page:02}.jpg")).collect(),
+            current_index: 0,
+        }
+    }
+
+    #[test]
+    fn test_set_current_index() {
+        let mut spine = pages(3);
+        spine.set_current_index(5);
+        assert_eq!(spine.current_index(), 2);
+        spine.go_to_next_index();
+        assert_eq!(spine.current_index(), 2);
+
+        let mut empty = pages(0);
+        empty.set_current_index(5);
+        assert_eq!(empty.current_index(), 0);
+        empty.go_to_next_index();
+        empty.go_to_prev_index();
+        assert_eq!(empty.current_index(), 0);
+        assert_eq!(empty.get_current_file_name(), None);
+    }
+}
@@ -0,0 +1,65 @@
+//! What a job does with the pages it fails to read or write, and the report of these failures.
+
+use std::fmt::Display;
+
+/// How a job handles an input (e.g. an image or an archive page) it fails to read or write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stops the job, returning the error.
+    FailFast,
+    /// Skips the input, the output being shorter.
+    #[default]
+    Skip,
+    /// Replaces the page by a blank one (see `Writer::insert_placeholder`), inputs that aren't
+    /// pages (e.g. an archive that can't be opened) are skipped.
+    Placeholder,
+}
+
+/// An input skipped (or replaced) by a job.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    /// The input, e.g. an image path or an archive path followed by the page name.
+    pub input: String,
+    pub error: String,
+    /// Whether the page was replaced by a placeholder.
+    pub placeholder: bool,
+}
+
+/// The outcome of a job which didn't fail.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// The number of pages written, placeholders included.
+    pub pages: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Whether every input was written.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Records the failure, unless the policy is to fail fast and the error is returned. Returns
+    /// whether a placeholder is to be inserted by the caller.
+    pub fn record<E: Display>(
+        &mut self,
+        policy: ErrorPolicy,
+        input: impl Display,
+        err: E,
+        is_page: bool,
+    ) -> Result<bool, E> {
+        if policy == ErrorPolicy::FailFast {
+            return Err(err);
+        }
+
+        let placeholder = is_page && policy == ErrorPolicy::Placeholder;
+        self.failures.push(Failure {
+            input: input.to_string(),
+            error: err.to_string(),
+            placeholder,
+        });
+
+        Ok(placeholder)
+    }
+}
@@ -0,0 +1,169 @@
+//! Table of contents of an archive, derived from its `ComicInfo.xml` bookmarks or its folders.
+
+use lexical_sort::{StringSort, natural_lexical_cmp};
+
+use crate::comic_info::ComicInfo;
+
+/// A chapter and its nested chapters, `index` being the spine index of its first page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+    pub index: usize,
+    pub children: Vec<Chapter>,
+}
+
+/// The chapters, depth first, with their depth (0 for top-level chapters).
+#[must_use]
+pub fn flatten(chapters: &[Chapter]) -> Vec<(usize, &Chapter)> {
+    let mut flattened = Vec::new();
+    let mut stack = chapters
+        .iter()
+        .rev()
+        .map(|chapter| (0, chapter))
+        .collect::<Vec<_>>();
+
+    while let Some((depth, chapter)) = stack.pop() {
+        flattened.push((depth, chapter));
+        stack.extend(
+            chapter
+                .children
+                .iter()
+                .rev()
+                .map(|child| (depth + 1, child)),
+        );
+    }
+
+    flattened
+}
+
+/// The spine indices chapters start at, sorted and deduplicated.
+pub(crate) fn starts(chapters: &[Chapter]) -> Vec<usize> {
+    let mut starts = flatten(chapters)
+        .into_iter()
+        .map(|(_, chapter)| chapter.index)
+        .collect::<Vec<_>>();
+    starts.sort_unstable();
+    starts.dedup();
+
+    starts
+}
+
+/// Chapters from the `ComicInfo` pages bookmarks, whose `image` is an index in the naturally
+/// sorted spine.
+pub(crate) fn from_bookmarks(spine: &[String], comic_info: &ComicInfo) -> Vec<Chapter> {
+    let mut natural = spine.to_vec();
+    natural.string_sort_unstable(natural_lexical_cmp);
+
+    let mut chapters = comic_info
+        .pages
+        .iter()
+        .filter_map(|page| {
+            let title = page.bookmark.as_ref()?;
+            let name = natural.get(page.image)?;
+            let index = spine.iter().position(|other| other == name)?;
+
+            Some(Chapter {
+                title: title.clone(),
+                index,
+                children: Vec::new(),
+            })
+        })
+        .collect::<Vec<_>>();
+    chapters.sort_by_key(|chapter| chapter.index);
+
+    chapters
+}
+
+/// Chapters from the spine entries folders, nested like them. The folders shared by all the
+/// entries (e.g. a single root folder) are not chapters, and a folder whose pages are not
+/// contiguous in the spine is a chapter for each run of pages.
+pub(crate) fn from_folders(spine: &[String]) -> Vec<Chapter> {
+    let folders = spine
+        .iter()
+        .map(|name| {
+            let mut components = name
+                .split('/')
+                .filter(|c| !c.is_empty())
+                .collect::<Vec<_>>();
+            components.pop();
+            components
+        })
+        .collect::<Vec<_>>();
+
+    let shared = folders.first().map_or(0, |first| {
+        folders
+            .iter()
+            .map(|components| common_len(first, components))
+            .min()
+            .unwrap_or_default()
+    });
+
+    let mut chapters = Vec::new();
+    let mut open: &[&str] = &[];
+
+    for (index, components) in folders.iter().enumerate() {
+        let components = &components[shared..];
+        let common = common_len(open, components);
+
+        for (depth, folder) in components.iter().enumerate().skip(common) {
+            last_children(&mut chapters, depth).push(Chapter {
+                title: (*folder).to_string(),
+                index,
+                children: Vec::new(),
+            });
+        }
+
+        open = components;
+    }
+
+    chapters
+}
+
+fn common_len(a: &[&str], b: &[&str]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// The children of the last chapter at the depth (the top-level chapters for 0).
+fn last_children(chapters: &mut Vec<Chapter>, depth: usize) -> &mut Vec<Chapter> {
+    if depth == 0 || chapters.is_empty() {
+        return chapters;
+    }
+
+    let last = chapters.len() - 1;
+    last_children(&mut chapters[last].children, depth - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_folders() {
+        let spine = [
+            "Berserk/Volume 1/Chapter 1/01.jpg",
+            "Berserk/Volume 1/Chapter 1/02.jpg",
+            "Berserk/Volume 1/Chapter 2/01.jpg",
+            "Berserk/Volume 2/01.jpg",
+            "Berserk/credits.jpg",
+        ]
+        .map(ToString::to_string);
+
+        let chapters = from_folders(&spine);
+        let flattened = flatten(&chapters)
+            .into_iter()
+            .map(|(depth, chapter)| (depth, chapter.title.as_str(), chapter.index))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            flattened,
+            [
+                (0, "Volume 1", 0),
+                (1, "Chapter 1", 0),
+                (1, "Chapter 2", 2),
+                (0, "Volume 2", 3),
+            ]
+        );
+        assert_eq!(starts(&chapters), [0, 2, 3]);
+        assert!(from_folders(&["01.jpg".to_string(), "02.jpg".to_string()]).is_empty());
+    }
+}
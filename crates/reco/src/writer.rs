@@ -1,21 +1,326 @@
 use std::{
-    fs::{File, create_dir_all},
-    io::{self, Cursor, Seek, Write},
+    collections::HashSet,
+    fs::{self, File, create_dir_all},
+    io::{self, Cursor, Read, Seek, Write},
+    ops::RangeInclusive,
     path::Path,
 };
 
-use image::{GenericImageView, PixelWithColorType, codecs::jpeg::JpegEncoder};
+use image::{
+    DynamicImage, GenericImageView, ImageError, ImageReader, PixelWithColorType, RgbImage,
+    codecs::jpeg::JpegEncoder,
+};
 use infer::is_image;
-use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+use tracing::warn;
+use zip::{CompressionMethod, ZipArchive, ZipWriter, write::SimpleFileOptions};
 
 use crate::{
-    ArchiveFinishError,
+    ArchiveFinishError, Reader,
+    comic_info::{COMIC_INFO_PATH, ComicInfo},
+    encoding::EncodingOptions,
+    epub::Package as EpubPackage,
     errors::{CreateArchiveError, InsertionError},
+    naming::{Naming, normalize_ext, sanitize},
+    output::TempOutput,
 };
 
+/// How the entries of a CBZ are compressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Stores the images already compressed by their format (e.g. JPEG or WebP), deflates the
+    /// other images (e.g. PNG, BMP or TIFF) and the sidecar entries.
+    #[default]
+    Auto,
+    Stored,
+    Deflate,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression levels of the method (of the images deflated by `Auto`), `None` for
+    /// `Stored`.
+    #[must_use]
+    pub fn levels(self) -> Option<RangeInclusive<i64>> {
+        match self {
+            Self::Auto | Self::Deflate => Some(0..=9),
+            Self::Stored => None,
+            Self::Zstd => Some(-7..=22),
+        }
+    }
+}
+
+/// The kind of archive produced by the [`Writer`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Cbz,
+    /// A fixed-layout EPUB 3, one page per image.
+    Epub,
+}
+
+impl Format {
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Cbz => "cbz",
+            Self::Epub => "epub",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    format: Format,
+    title: Option<String>,
+    encoding: Option<EncodingOptions>,
+    comic_info: Option<ComicInfo>,
+    no_clobber: bool,
+    naming: Naming,
+    compression: Compression,
+    compression_level: Option<i64>,
+    sidecars: Vec<(String, Vec<u8>)>,
+}
+
+impl Options {
+    #[must_use]
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            title: None,
+            encoding: None,
+            comic_info: None,
+            no_clobber: false,
+            naming: Naming::default(),
+            compression: Compression::default(),
+            compression_level: None,
+            sidecars: Vec::new(),
+        }
+    }
+
+    /// How the inserted pages and sidecar entries are compressed (CBZ only), pages copied without
+    /// being decompressed keep their compression.
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// The deflate (0 to 9) or zstd (-7 to 22) compression level, the default one if not set. It
+    /// isn't checked, the [`Compression::levels`] are.
+    #[must_use]
+    pub fn with_compression_level(mut self, level: i64) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// A non-image entry (e.g. a metadata file) inserted when the archive is finished, see
+    /// [`Writer::insert_sidecar`].
+    #[must_use]
+    pub fn with_sidecar(mut self, name: impl Into<String>, buf: Vec<u8>) -> Self {
+        self.sidecars.push((name.into(), buf));
+        self
+    }
+
+    /// How the inserted pages are named (CBZ only), zero-padded indexes by default.
+    #[must_use]
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Fails instead of replacing an existing archive, when created from a path.
+    #[must_use]
+    pub fn with_no_clobber(mut self, no_clobber: bool) -> Self {
+        self.no_clobber = no_clobber;
+        self
+    }
+
+    /// The publication title, only used by formats that store one (e.g. EPUB).
+    #[must_use]
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Re-encodes every inserted image, including the already encoded ones (which are otherwise
+    /// inserted unchanged).
+    #[must_use]
+    pub fn with_encoding(mut self, encoding: EncodingOptions) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// The metadata embedded as `ComicInfo.xml` (CBZ only), its title is also used as the
+    /// publication title by formats that store one, when none is set.
+    #[must_use]
+    pub fn with_comic_info(mut self, comic_info: ComicInfo) -> Self {
+        self.comic_info = Some(comic_info);
+        self
+    }
+
+    #[must_use]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    #[must_use]
+    pub fn no_clobber(&self) -> bool {
+        self.no_clobber
+    }
+
+    #[must_use]
+    pub fn encoding(&self) -> Option<&EncodingOptions> {
+        self.encoding.as_ref()
+    }
+
+    #[must_use]
+    pub fn comic_info(&self) -> Option<&ComicInfo> {
+        self.comic_info.as_ref()
+    }
+}
+
+/// A page encoded by an [`Encoder`], ready to be inserted by a [`Writer`].
+#[derive(Debug, Clone)]
+pub struct EncodedPage {
+    ext: String,
+    buf: Vec<u8>,
+    dimensions: Option<(u32, u32)>,
+    /// The source file name (without extension), for the [`Naming::Original`] entry names.
+    name: Option<String>,
+}
+
+impl EncodedPage {
+    /// Sets the source file name (without extension) of the page.
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// The encoded image size, in bytes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+/// The dimensions of the blank pages replacing the pages which couldn't be read.
+const PLACEHOLDER_DIMENSIONS: (u32, u32) = (1000, 1500);
+
+/// Encodes pages as the [`Writer`] it comes from, without borrowing it (e.g. on other threads).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Encoder {
+    encoding: Option<EncodingOptions>,
+}
+
+impl Encoder {
+    /// Encodes the image using the encoding options (or the default ones).
+    pub fn encode_image(&self, img: &DynamicImage) -> Result<EncodedPage, InsertionError> {
+        let encoding = self.encoding.unwrap_or_default();
+        let buf = encoding.encode(img).map_err(InsertionError::ImageEncode)?;
+
+        Ok(EncodedPage {
+            ext: format!(".{}", encoding.format().extension()),
+            buf,
+            dimensions: Some(img.dimensions()),
+            name: None,
+        })
+    }
+
+    /// Encodes an encoded image, kept unchanged unless encoding options were explicitly provided.
+    pub fn encode_buf(&self, ext: &str, buf: Vec<u8>) -> Result<EncodedPage, InsertionError> {
+        if self.encoding.is_none() {
+            return Ok(EncodedPage {
+                ext: ext.to_string(),
+                buf,
+                dimensions: None,
+                name: None,
+            });
+        }
+
+        let img = ImageReader::new(Cursor::new(buf))
+            .with_guessed_format()
+            .map_err(|err| InsertionError::ImageDecode(ImageError::IoError(err)))?
+            .decode()
+            .map_err(InsertionError::ImageDecode)?;
+
+        self.encode_image(&img)
+    }
+
+    /// Encodes a blank (white) page.
+    pub fn encode_placeholder(&self) -> Result<EncodedPage, InsertionError> {
+        let (width, height) = PLACEHOLDER_DIMENSIONS;
+        let img = RgbImage::from_pixel(width, height, [255, 255, 255].into());
+
+        self.encode_image(&DynamicImage::ImageRgb8(img))
+    }
+
+    pub fn encode_path(&self, path: &Path) -> Result<EncodedPage, InsertionError> {
+        let buf = fs::read(path).map_err(InsertionError::FileOpen)?;
+        let (stem, ext) = split_file_name(path);
+
+        let mut page = self.encode_buf(ext, buf)?;
+        page.name = stem.map(ToString::to_string);
+
+        Ok(page)
+    }
+}
+
+/// Whether the image format is already compressed, e.g. JPEG or WebP (which is also lossless).
+fn is_compressed_image(buf: &[u8]) -> bool {
+    infer::get(buf).is_some_and(|kind| {
+        matches!(
+            kind.mime_type(),
+            "image/jpeg" | "image/webp" | "image/avif" | "image/gif" | "image/jxl" | "image/heif"
+        )
+    })
+}
+
+/// The file stem and extension (empty if none) of a path.
+fn split_file_name(path: &Path) -> (Option<&str>, &str) {
+    let stem = path.file_stem().and_then(|stem| stem.to_str());
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+
+    (stem, ext)
+}
+
+/// The name (with its folders, without extension) and extension (empty if none) of an entry, the
+/// name being the source name of [`EncodedPage::with_name`].
+#[must_use]
+pub fn split_entry_name(name: &str) -> (Option<&str>, &str) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('/') && !stem.is_empty() && !stem.ends_with('/') => {
+            (Some(stem), ext)
+        }
+        _ => (Some(name), ""),
+    }
+}
+
 pub struct Writer<W: Write + Seek> {
     archive: ZipWriter<W>,
     current_index: usize,
+    epub: Option<EpubPackage>,
+    encoding: Option<EncodingOptions>,
+    comic_info: Option<ComicInfo>,
+    /// Whether the archive appended to already has a `ComicInfo.xml`, which can't be replaced.
+    has_comic_info: bool,
+    /// The path the archive is moved to once finished, when created from a path.
+    output: Option<TempOutput>,
+    naming: Naming,
+    /// The entry names, kept unique.
+    names: HashSet<String>,
+    chapter: usize,
+    compression: Compression,
+    compression_level: Option<i64>,
+    sidecars: Vec<(String, Vec<u8>)>,
 }
 
 impl<W> Writer<W>
@@ -23,22 +328,125 @@ where
     W: Write + Seek,
 {
     pub fn new(writer: W) -> Self {
+        Self::with_options(writer, Options::default())
+    }
+
+    pub fn with_options(writer: W, opts: Options) -> Self {
         let archive = ZipWriter::new(writer);
+        let epub = match opts.format {
+            Format::Cbz => None,
+            Format::Epub => Some(EpubPackage::new(opts.title.or_else(|| {
+                opts.comic_info
+                    .as_ref()
+                    .and_then(|comic_info| comic_info.title.clone())
+            }))),
+        };
 
         Self {
             archive,
             current_index: 0,
+            epub,
+            encoding: opts.encoding,
+            comic_info: opts.comic_info,
+            has_comic_info: false,
+            output: None,
+            naming: opts.naming,
+            names: HashSet::new(),
+            chapter: 0,
+            compression: opts.compression,
+            compression_level: opts.compression_level,
+            sidecars: opts.sidecars,
         }
     }
 }
 
+impl<W> Writer<W>
+where
+    W: Read + Write + Seek,
+{
+    /// Opens an existing cbz to insert pages after its own, which are kept as is (and so is its
+    /// `ComicInfo.xml` if it has one, zip entries can't be replaced). New pages are numbered after
+    /// the existing numbered ones.
+    pub fn append(mut writer: W, opts: Options) -> Result<Self, CreateArchiveError> {
+        if opts.format == Format::Epub {
+            return Err(CreateArchiveError::EpubAppend);
+        }
+
+        let archive = ZipArchive::new(&mut writer).map_err(CreateArchiveError::Append)?;
+        let current_index = archive
+            .file_names()
+            .filter_map(|name| Path::new(name).file_stem()?.to_str()?.parse::<usize>().ok())
+            .max()
+            .map_or(0, |index| index.saturating_add(1));
+        let has_comic_info = archive
+            .file_names()
+            .any(|name| name.eq_ignore_ascii_case(COMIC_INFO_PATH));
+        let names = archive.file_names().map(ToString::to_string).collect();
+        drop(archive);
+
+        Ok(Self {
+            archive: ZipWriter::new_append(writer).map_err(CreateArchiveError::Append)?,
+            current_index,
+            epub: None,
+            encoding: opts.encoding,
+            comic_info: opts.comic_info,
+            has_comic_info,
+            output: None,
+            naming: opts.naming,
+            names,
+            chapter: 0,
+            compression: opts.compression,
+            compression_level: opts.compression_level,
+            sidecars: opts.sidecars,
+        })
+    }
+}
+
 impl Writer<File> {
     pub fn create_from_path(path: &Path) -> Result<Self, CreateArchiveError> {
+        Self::create_from_path_with_options(path, Options::default())
+    }
+
+    /// Creates the archive in a temporary file, moved to the path once finished.
+    pub fn create_from_path_with_options(
+        path: &Path,
+        opts: Options,
+    ) -> Result<Self, CreateArchiveError> {
+        if opts.no_clobber && path.exists() {
+            return Err(CreateArchiveError::AlreadyExists(path.to_path_buf()));
+        }
+
         create_dir_all(path.parent().unwrap_or_else(|| Path::new(".")))
             .map_err(CreateArchiveError::DirCreation)?;
-        let file = File::create(path).map_err(CreateArchiveError::ArchiveCreation)?;
+        let (file, output) = TempOutput::create(path, opts.no_clobber)
+            .map_err(CreateArchiveError::ArchiveCreation)?;
+
+        let mut writer = Self::with_options(file, opts);
+        writer.output = Some(output);
+
+        Ok(writer)
+    }
 
-        Ok(Self::new(file))
+    pub fn append_from_path(path: &Path) -> Result<Self, CreateArchiveError> {
+        Self::append_from_path_with_options(path, Options::default())
+    }
+
+    /// Appends to a copy of the archive, which replaces it once finished.
+    pub fn append_from_path_with_options(
+        path: &Path,
+        opts: Options,
+    ) -> Result<Self, CreateArchiveError> {
+        let (mut file, output) =
+            TempOutput::create(path, false).map_err(CreateArchiveError::ArchiveCreation)?;
+        File::open(path)
+            .and_then(|mut archive| io::copy(&mut archive, &mut file))
+            .and_then(|_| file.rewind())
+            .map_err(CreateArchiveError::ArchiveCreation)?;
+
+        let mut writer = Self::append(file, opts)?;
+        writer.output = Some(output);
+
+        Ok(writer)
     }
 }
 
@@ -46,14 +454,16 @@ impl<W> Writer<W>
 where
     W: Write + Seek,
 {
+    /// The encoder of the inserted pages.
+    pub fn encoder(&self) -> Encoder {
+        Encoder {
+            encoding: self.encoding,
+        }
+    }
+
     pub fn insert_from_path(&mut self, path: &Path) -> Result<(), InsertionError> {
-        let file = File::open(path).map_err(InsertionError::FileOpen)?;
-        let ext = path
-            .extension()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or_default();
-        self.insert_reader(ext, file)
+        let page = self.encoder().encode_path(path)?;
+        self.insert_encoded(&page)
     }
 
     pub fn insert_reader(
@@ -80,10 +490,150 @@ where
             .encode_image(img)
             .map_err(InsertionError::ImageEncode)?;
 
-        self.insert_buf(".jpeg", &buf.into_inner())
+        self.write_buf(".jpeg", &buf.into_inner(), Some(img.dimensions()), None)
+    }
+
+    /// Encodes the image using the writer encoding options (or the default ones).
+    pub fn insert_image(&mut self, img: &DynamicImage) -> Result<(), InsertionError> {
+        let page = self.encoder().encode_image(img)?;
+        self.insert_encoded(&page)
     }
 
+    /// Inserts an encoded image, unchanged unless encoding options were explicitly provided.
     pub fn insert_buf(&mut self, ext: &str, buf: &[u8]) -> Result<(), InsertionError> {
+        if self.encoding.is_none() {
+            return self.write_buf(ext, buf, None, None);
+        }
+
+        let page = self.encoder().encode_buf(ext, buf.to_vec())?;
+        self.insert_encoded(&page)
+    }
+
+    /// Inserts a blank page, replacing a page which couldn't be read.
+    pub fn insert_placeholder(&mut self) -> Result<(), InsertionError> {
+        let page = self.encoder().encode_placeholder()?;
+        self.insert_encoded(&page)
+    }
+
+    /// Inserts a page encoded by the writer [`Encoder`].
+    pub fn insert_encoded(&mut self, page: &EncodedPage) -> Result<(), InsertionError> {
+        self.write_buf(&page.ext, &page.buf, page.dimensions, page.name.as_deref())
+    }
+
+    /// Inserts a non-image entry (e.g. a metadata file), which isn't a page: it isn't numbered nor
+    /// named by the naming policy. The name can include folders (e.g. `extras/notes.txt`) and is
+    /// sanitised, it can't be an existing entry nor the `ComicInfo.xml` (CBZ only).
+    pub fn insert_sidecar(&mut self, name: &str, buf: &[u8]) -> Result<(), InsertionError> {
+        let name = self.sidecar_name(name)?;
+        let options = self.file_options(None);
+        self.archive
+            .start_file(name.as_str(), options)
+            .map_err(InsertionError::FileCreation)?;
+        self.archive
+            .write_all(buf)
+            .map_err(InsertionError::FileWrite)?;
+        self.names.insert(name);
+
+        Ok(())
+    }
+
+    /// Copies an entry of the archive read which isn't a page (e.g. a metadata file), without
+    /// decompressing it when both are zip archives. It is named as by [`Writer::insert_sidecar`].
+    /// Returns the number of bytes copied (compressed or not).
+    pub fn copy_raw_sidecar(
+        &mut self,
+        reader: &mut Reader,
+        file_name: &str,
+    ) -> Result<u64, InsertionError> {
+        let name = self.sidecar_name(file_name)?;
+        let Some(file) = reader
+            .zip_entry(file_name)
+            .map_err(InsertionError::EntryRead)?
+        else {
+            let buf = reader.read(file_name).map_err(InsertionError::EntryRead)?;
+            self.insert_sidecar(&name, &buf)?;
+            return Ok(buf.len() as u64);
+        };
+
+        let size = file.compressed_size();
+        self.archive
+            .raw_copy_file_rename(file, name.as_str())
+            .map_err(InsertionError::FileCreation)?;
+        self.names.insert(name);
+
+        Ok(size)
+    }
+
+    /// The sanitised name of a sidecar entry, which must be new.
+    fn sidecar_name(&self, name: &str) -> Result<String, InsertionError> {
+        if self.epub.is_some() {
+            return Err(InsertionError::EpubSidecar);
+        }
+
+        let name = name
+            .split(['/', '\\'])
+            .map(sanitize)
+            .filter(|component| !component.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        if name.is_empty()
+            || name.eq_ignore_ascii_case(COMIC_INFO_PATH)
+            || self.names.contains(&name)
+        {
+            return Err(InsertionError::SidecarName(name));
+        }
+
+        Ok(name)
+    }
+
+    /// The options of an entry, an image or a sidecar entry (without an image).
+    fn file_options(&self, image: Option<&[u8]>) -> SimpleFileOptions {
+        let method = match self.compression {
+            Compression::Auto if image.is_some_and(is_compressed_image) => {
+                CompressionMethod::Stored
+            }
+            Compression::Auto | Compression::Deflate => CompressionMethod::Deflated,
+            Compression::Stored => CompressionMethod::Stored,
+            Compression::Zstd => CompressionMethod::Zstd,
+        };
+        let options = SimpleFileOptions::default().compression_method(method);
+
+        if method == CompressionMethod::Stored {
+            options
+        } else {
+            options.compression_level(self.compression_level)
+        }
+    }
+
+    /// Starts a new chapter, numbering the `{chapter}` of the [`Naming::Template`] entry names and
+    /// prefixing the [`Naming::Original`] ones.
+    pub fn start_chapter(&mut self) {
+        self.chapter += 1;
+    }
+
+    /// A unique entry name for the page inserted next, from the naming policy.
+    fn entry_name(&mut self, ext: &str, source: Option<&str>) -> String {
+        let stem = self.naming.name(self.current_index, self.chapter, source);
+        let ext = normalize_ext(ext);
+
+        let mut name = format!("{stem}{ext}");
+        let mut duplicate = 1;
+        while self.names.contains(&name) {
+            duplicate += 1;
+            name = format!("{stem} ({duplicate}){ext}");
+        }
+        self.names.insert(name.clone());
+
+        name
+    }
+
+    fn write_buf(
+        &mut self,
+        ext: &str,
+        buf: &[u8],
+        dimensions: Option<(u32, u32)>,
+        source: Option<&str>,
+    ) -> Result<(), InsertionError> {
         if self.is_full() {
             return Err(InsertionError::CbzFull(usize::MAX));
         }
@@ -92,8 +642,20 @@ where
             return Err(InsertionError::InvalidFormat);
         }
 
-        let name = format!("{:0>20}{ext}", self.current_index);
-        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        if let Some(epub) = &mut self.epub {
+            match dimensions {
+                Some((width, height)) => {
+                    epub.insert_page_with_dimensions(&mut self.archive, buf, width, height)?;
+                }
+                None => epub.insert_page(&mut self.archive, buf)?,
+            }
+            self.current_index += 1;
+
+            return Ok(());
+        }
+
+        let name = self.entry_name(ext, source);
+        let options = self.file_options(Some(buf));
         self.archive
             .start_file(name, options)
             .map_err(InsertionError::FileCreation)?;
@@ -108,6 +670,70 @@ where
         Ok(())
     }
 
+    /// Copies a page of the archive read, without decompressing it when both are zip archives and
+    /// the pages aren't re-encoded. It is inserted like any other buffer otherwise. Returns the
+    /// number of bytes copied (compressed or not).
+    pub fn copy_raw(
+        &mut self,
+        reader: &mut Reader,
+        file_name: &str,
+    ) -> Result<u64, InsertionError> {
+        if self.encoding.is_some() || self.epub.is_some() {
+            return self.copy_decompressed(reader, file_name);
+        }
+
+        let Some(file) = reader
+            .zip_entry(file_name)
+            .map_err(InsertionError::EntryRead)?
+        else {
+            return self.copy_decompressed(reader, file_name);
+        };
+
+        if self.is_full() {
+            return Err(InsertionError::CbzFull(usize::MAX));
+        }
+
+        let size = file.compressed_size();
+        let (stem, ext) = split_entry_name(file_name);
+        let name = self.entry_name(ext, stem);
+        self.archive
+            .raw_copy_file_rename(file, name)
+            .map_err(InsertionError::FileCreation)?;
+        self.current_index += 1;
+
+        Ok(size)
+    }
+
+    fn copy_decompressed(
+        &mut self,
+        reader: &mut Reader,
+        file_name: &str,
+    ) -> Result<u64, InsertionError> {
+        let buf = reader.read(file_name).map_err(InsertionError::EntryRead)?;
+        let len = buf.len() as u64;
+
+        let (stem, ext) = split_entry_name(file_name);
+        let mut page = self.encoder().encode_buf(ext, buf)?;
+        page.name = stem.map(ToString::to_string);
+        self.insert_encoded(&page)?;
+
+        Ok(len)
+    }
+
+    /// Replaces the metadata written when the archive is finished.
+    pub fn set_comic_info(&mut self, comic_info: Option<ComicInfo>) {
+        self.comic_info = comic_info;
+    }
+
+    pub fn comic_info(&self) -> Option<&ComicInfo> {
+        self.comic_info.as_ref()
+    }
+
+    /// Sets the zip comment.
+    pub fn set_comment(&mut self, comment: &[u8]) {
+        self.archive.set_raw_comment(comment.into());
+    }
+
     pub fn is_empty(&self) -> bool {
         self.current_index == 0
     }
@@ -116,7 +742,31 @@ where
         self.current_index == usize::MAX
     }
 
-    pub fn finish(self) -> Result<W, ArchiveFinishError> {
-        self.archive.finish().map_err(Into::into)
+    pub fn finish(mut self) -> Result<W, ArchiveFinishError> {
+        for (name, buf) in std::mem::take(&mut self.sidecars) {
+            self.insert_sidecar(&name, &buf)
+                .map_err(ArchiveFinishError::Sidecar)?;
+        }
+
+        if let Some(epub) = self.epub.take() {
+            epub.finish(&mut self.archive)?;
+        } else if self.has_comic_info {
+            if self.comic_info.is_some() {
+                warn!("the appended archive ComicInfo.xml is kept, it can't be replaced");
+            }
+        } else if let Some(comic_info) = self.comic_info.take().filter(|info| !info.is_empty()) {
+            let options = self.file_options(None);
+            self.archive.start_file(COMIC_INFO_PATH, options)?;
+            self.archive
+                .write_all(comic_info.to_xml().as_bytes())
+                .map_err(zip::result::ZipError::Io)?;
+        }
+
+        let writer = self.archive.finish()?;
+        if let Some(output) = self.output.take() {
+            output.persist()?;
+        }
+
+        Ok(writer)
     }
 }